@@ -0,0 +1,336 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+pub const JUDGER_VERSION: &str = "2.1.1";
+
+// How a /judge request is answered.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reply {
+    Accepted,
+    WrongAnswer,
+    TimeLimitExceeded,
+    CompileError,
+    SystemError,
+    // answer after the delay
+    Slow(Duration, Box<Reply>),
+    // close the connection without answering
+    Dropped,
+}
+
+impl Reply {
+    pub fn from_name(name: &str) -> Option<Reply> {
+        match name {
+            "ac" => Some(Reply::Accepted),
+            "wa" => Some(Reply::WrongAnswer),
+            "tle" => Some(Reply::TimeLimitExceeded),
+            "ce" => Some(Reply::CompileError),
+            "se" => Some(Reply::SystemError),
+            "slow" => Some(Reply::Slow(
+                Duration::from_secs(3),
+                Box::new(Reply::Accepted),
+            )),
+            "drop" => Some(Reply::Dropped),
+            _ => None,
+        }
+    }
+
+    // a `mock:<name>` marker in the source code picks the reply,
+    // so submissions made through the API can choose their verdict
+    fn from_src(src: &str) -> Option<Reply> {
+        let start = src.find("mock:")? + "mock:".len();
+        let name: String = src[start..]
+            .chars()
+            .take_while(|c| c.is_ascii_alphabetic())
+            .collect();
+        Reply::from_name(&name)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct JudgeRequest {
+    src: String,
+    test_case: Option<Vec<Value>>,
+}
+
+fn detail(test_case: usize, result: i32, cpu_time: i32) -> Value {
+    json!({
+        "cpu_time": cpu_time,
+        "real_time": cpu_time + 1,
+        "memory": 1024 * 1024,
+        "signal": 0,
+        "exit_code": 0,
+        "error": 0,
+        "result": result,
+        "test_case": test_case.to_string(),
+        "output_md5": null,
+        "output": null,
+    })
+}
+
+// Build the body QingdaoU JudgeServer would answer with, the last test case carries the verdict.
+fn judge_payload(reply: &Reply, request: &JudgeRequest) -> Value {
+    let case_count = request
+        .test_case
+        .as_ref()
+        .map(|test_case| test_case.len())
+        .unwrap_or(2)
+        .max(1);
+    let last_result = match reply {
+        Reply::CompileError => {
+            return json!({
+                "err": "CompileError",
+                "data": "main.cpp:1:1: error: expected unqualified-id before 'mock'",
+            });
+        }
+        Reply::WrongAnswer => (-1, 1),
+        Reply::TimeLimitExceeded => (1, 1001),
+        Reply::SystemError => (5, 0),
+        _ => (0, 1),
+    };
+
+    let mut data = Vec::new();
+    for test_case in 1..case_count {
+        data.push(detail(test_case, 0, 1));
+    }
+    data.push(detail(case_count, last_result.0, last_result.1));
+    json!({ "err": null, "data": data })
+}
+
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+pub fn server_info(service_url: &str) -> Value {
+    json!({
+        "judger_version": JUDGER_VERSION,
+        "hostname": "mock-judge-server",
+        "running_task_number": 0,
+        "cpu_core": 2,
+        "memory": 10.0,
+        "cpu": 5.0,
+        "service_url": service_url,
+    })
+}
+
+struct State {
+    token: Option<String>,
+    default_reply: Reply,
+    script: Mutex<VecDeque<Reply>>,
+    judge_count: AtomicUsize,
+}
+
+pub struct MockJudgeServer {
+    addr: SocketAddr,
+    state: Arc<State>,
+}
+
+impl MockJudgeServer {
+    // Listen on a random local port and accept any token.
+    pub fn start(default_reply: Reply) -> io::Result<MockJudgeServer> {
+        MockJudgeServer::bind("127.0.0.1:0", None, default_reply)
+    }
+
+    pub fn bind(
+        addr: &str,
+        token: Option<String>,
+        default_reply: Reply,
+    ) -> io::Result<MockJudgeServer> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(State {
+            token,
+            default_reply,
+            script: Mutex::new(VecDeque::new()),
+            judge_count: AtomicUsize::new(0),
+        });
+
+        let accept_state = state.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let state = accept_state.clone();
+                thread::spawn(move || {
+                    if let Err(e) = handle(stream, &state) {
+                        eprintln!("mock judge server: {}", e);
+                    }
+                });
+            }
+        });
+
+        Ok(MockJudgeServer { addr, state })
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    // Queue a reply for the next /judge request, the default reply is used once the script runs out.
+    pub fn push(&self, reply: Reply) {
+        self.state.script.lock().unwrap().push_back(reply);
+    }
+
+    pub fn judge_count(&self) -> usize {
+        self.state.judge_count.load(Ordering::SeqCst)
+    }
+}
+
+struct Request {
+    path: String,
+    token: Option<String>,
+    body: Vec<u8>,
+}
+
+fn read_request(reader: &mut BufReader<TcpStream>) -> io::Result<Request> {
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let path = request_line
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or("/")
+        .to_owned();
+
+    let mut token = None;
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            match name.trim().to_ascii_lowercase().as_str() {
+                "content-length" => content_length = value.trim().parse().unwrap_or(0),
+                "x-judge-server-token" => token = Some(value.trim().to_owned()),
+                _ => (),
+            }
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(Request { path, token, body })
+}
+
+fn write_response(stream: &mut TcpStream, status: &str, body: &Value) -> io::Result<()> {
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+fn handle(stream: TcpStream, state: &State) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut stream = stream;
+    let request = read_request(&mut reader)?;
+
+    if let Some(token) = state.token.as_ref() {
+        let received = request.token.clone().unwrap_or_default();
+        if received != *token && received != hash_token(token) {
+            let body = json!({ "err": "TokenVerificationFailed", "data": "invalid token" });
+            return write_response(&mut stream, "200 OK", &body);
+        }
+    }
+
+    match request.path.as_str() {
+        "/ping" => {
+            let mut info = server_info(&format!("http://{}", stream.local_addr()?));
+            info["action"] = json!("pong");
+            write_response(&mut stream, "200 OK", &json!({ "err": null, "data": info }))
+        }
+        "/judge" => {
+            let judge_request = match serde_json::from_slice::<JudgeRequest>(&request.body) {
+                Ok(judge_request) => judge_request,
+                Err(e) => {
+                    let body = json!({ "err": "JudgeClientError", "data": e.to_string() });
+                    return write_response(&mut stream, "200 OK", &body);
+                }
+            };
+            state.judge_count.fetch_add(1, Ordering::SeqCst);
+
+            let mut reply = match Reply::from_src(&judge_request.src) {
+                Some(reply) => reply,
+                None => state
+                    .script
+                    .lock()
+                    .unwrap()
+                    .pop_front()
+                    .unwrap_or_else(|| state.default_reply.clone()),
+            };
+            while let Reply::Slow(delay, inner) = reply {
+                thread::sleep(delay);
+                reply = *inner;
+            }
+            if reply == Reply::Dropped {
+                return stream.shutdown(Shutdown::Both);
+            }
+
+            write_response(
+                &mut stream,
+                "200 OK",
+                &judge_payload(&reply, &judge_request),
+            )
+        }
+        _ => write_response(&mut stream, "404 Not Found", &json!({ "err": "NotFound" })),
+    }
+}
+
+// A minimal blocking HTTP/1.1 POST, returns the status code and body.
+pub fn post(url: &str, headers: &[(&str, &str)], body: &str) -> io::Result<(u16, String)> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidInput, format!("bad url {}", url));
+    let rest = url.strip_prefix("http://").ok_or_else(invalid)?;
+    let (host, path) = match rest.find('/') {
+        Some(index) => (&rest[..index], &rest[index..]),
+        None => (rest, "/"),
+    };
+
+    let mut stream = TcpStream::connect(host)?;
+    let mut request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        path,
+        host,
+        body.len()
+    );
+    for (name, value) in headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str("\r\n");
+    request.push_str(body);
+    stream.write_all(request.as_bytes())?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    let status = response
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "bad response"))?;
+    let body = match response.find("\r\n\r\n") {
+        Some(index) => response[index + 4..].to_owned(),
+        None => String::new(),
+    };
+    Ok((status, body))
+}
+
+// Report to the backend the same way JudgeServer does, with the sha256 of the token.
+pub fn send_heartbeat(backend_url: &str, token: &str, service_url: &str) -> io::Result<u16> {
+    let mut body = server_info(service_url);
+    body["action"] = json!("heartbeat");
+    let hashed_token = hash_token(token);
+    let (status, _) = post(
+        backend_url,
+        &[("X-Judge-Server-Token", &hashed_token)],
+        &body.to_string(),
+    )?;
+    Ok(status)
+}
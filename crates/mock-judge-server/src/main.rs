@@ -0,0 +1,57 @@
+use mock_judge_server::{send_heartbeat, MockJudgeServer, Reply};
+use std::thread;
+use std::time::Duration;
+use structopt::StructOpt;
+
+/// Stand-in for QingdaoU JudgeServer, answers judge requests with scripted verdicts
+#[derive(StructOpt, Debug)]
+#[structopt(name = "mock-judge-server")]
+struct Opt {
+    /// Address to listen to
+    #[structopt(long, env = "LISTEN", default_value = "127.0.0.1:12345")]
+    listen: String,
+
+    /// Heartbeat URL of the backend, no heartbeat is sent when missing
+    #[structopt(long, env = "BACKEND_URL")]
+    backend_url: Option<String>,
+
+    /// URL the backend sends judge requests to, defaults to the listen address
+    #[structopt(long, env = "SERVICE_URL")]
+    service_url: Option<String>,
+
+    /// Token shared with the backend
+    #[structopt(long, env = "TOKEN", default_value = "YOUR_TOKEN_HERE")]
+    token: String,
+
+    /// Reply to judge requests without a `mock:<reply>` marker in their source code
+    #[structopt(
+        long,
+        default_value = "ac",
+        possible_values = &["ac", "wa", "tle", "ce", "se", "slow", "drop"]
+    )]
+    reply: String,
+
+    /// Seconds between two heartbeats
+    #[structopt(long, default_value = "3")]
+    heartbeat_interval_in_sec: u64,
+}
+
+fn main() -> std::io::Result<()> {
+    let opt = Opt::from_args();
+
+    let reply = Reply::from_name(&opt.reply).unwrap();
+    let server = MockJudgeServer::bind(&opt.listen, Some(opt.token.clone()), reply)?;
+    let service_url = opt.service_url.clone().unwrap_or_else(|| server.url());
+    println!("Mock judge server listening on {}", server.url());
+
+    loop {
+        if let Some(backend_url) = opt.backend_url.as_ref() {
+            match send_heartbeat(backend_url, &opt.token, &service_url) {
+                Ok(200) => (),
+                Ok(status) => eprintln!("Heartbeat rejected with {}", status),
+                Err(e) => eprintln!("Error sending heartbeat: {}", e),
+            }
+        }
+        thread::sleep(Duration::from_secs(opt.heartbeat_interval_in_sec));
+    }
+}
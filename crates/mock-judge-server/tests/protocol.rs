@@ -0,0 +1,85 @@
+use mock_judge_server::*;
+use serde_json::Value;
+use std::time::Duration;
+
+fn judge(server: &MockJudgeServer, src: &str) -> Value {
+    let body = serde_json::json!({ "src": src, "test_case": [{}, {}, {}] }).to_string();
+    let (status, body) = post(&format!("{}/judge", server.url()), &[], &body).unwrap();
+    assert_eq!(status, 200);
+    serde_json::from_str(&body).unwrap()
+}
+
+fn last_result(res: &Value) -> i64 {
+    let data = res["data"].as_array().unwrap();
+    assert_eq!(data.len(), 3);
+    data.last().unwrap()["result"].as_i64().unwrap()
+}
+
+#[test]
+fn ping() {
+    let server = MockJudgeServer::start(Reply::Accepted).unwrap();
+    let (status, body) = post(&format!("{}/ping", server.url()), &[], "{}").unwrap();
+    assert_eq!(status, 200);
+
+    let res: Value = serde_json::from_str(&body).unwrap();
+    assert!(res["err"].is_null());
+    assert_eq!(res["data"]["action"], "pong");
+    assert_eq!(res["data"]["judger_version"], JUDGER_VERSION);
+}
+
+#[test]
+fn scripted_replies() {
+    let server = MockJudgeServer::start(Reply::Accepted).unwrap();
+    server.push(Reply::WrongAnswer);
+    server.push(Reply::TimeLimitExceeded);
+    server.push(Reply::SystemError);
+    server.push(Reply::CompileError);
+
+    assert_eq!(last_result(&judge(&server, "")), -1);
+    assert_eq!(last_result(&judge(&server, "")), 1);
+    assert_eq!(last_result(&judge(&server, "")), 5);
+    let res = judge(&server, "");
+    assert_eq!(res["err"], "CompileError");
+    assert!(res["data"].is_string());
+    // script runs out
+    assert_eq!(last_result(&judge(&server, "")), 0);
+    assert_eq!(server.judge_count(), 5);
+}
+
+#[test]
+fn source_marker_overrides_script() {
+    let server = MockJudgeServer::start(Reply::Accepted).unwrap();
+    server.push(Reply::Accepted);
+    assert_eq!(
+        last_result(&judge(&server, "// mock:wa\nint main() {}")),
+        -1
+    );
+}
+
+#[test]
+fn slow_and_dropped() {
+    let server = MockJudgeServer::start(Reply::Accepted).unwrap();
+    server.push(Reply::Slow(
+        Duration::from_millis(200),
+        Box::new(Reply::Accepted),
+    ));
+    server.push(Reply::Dropped);
+
+    assert_eq!(last_result(&judge(&server, "")), 0);
+    let res = post(&format!("{}/judge", server.url()), &[], "{\"src\": \"\"}");
+    assert!(res.is_err());
+}
+
+#[test]
+fn token_verification() {
+    let server =
+        MockJudgeServer::bind("127.0.0.1:0", Some("secret".to_owned()), Reply::Accepted).unwrap();
+    let url = format!("{}/ping", server.url());
+
+    let (_, body) = post(&url, &[("X-Judge-Server-Token", "wrong")], "{}").unwrap();
+    assert!(body.contains("TokenVerificationFailed"));
+
+    let hashed_token = hash_token("secret");
+    let (_, body) = post(&url, &[("X-Judge-Server-Token", &hashed_token)], "{}").unwrap();
+    assert!(body.contains("pong"));
+}
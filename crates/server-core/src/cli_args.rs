@@ -12,6 +12,58 @@ pub struct Opt {
     #[structopt(short, long, env = "JUDGE_ACTOR_COUNT", default_value = "1")]
     pub judge_actor_count: usize,
 
+    /// Seconds to wait for a judge server to answer a judge request
+    #[structopt(long, env = "JUDGE_TIMEOUT_IN_SEC", default_value = "120")]
+    pub judge_timeout_in_sec: u64,
+
+    /// Seconds after which a claimed but unfinished judge task is considered lost,
+    /// should be longer than the judge timeout
+    #[structopt(long, env = "JUDGE_LEASE_TIMEOUT_IN_SEC", default_value = "300")]
+    pub judge_lease_timeout_in_sec: u64,
+
+    /// Times a submission is sent to judge servers before it is given up as SystemError
+    #[structopt(long, env = "JUDGE_MAX_ATTEMPTS", default_value = "3")]
+    pub judge_max_attempts: i32,
+
+    /// Seconds to wait before the first retry of a failed judge task, doubled on every retry
+    #[structopt(long, env = "JUDGE_RETRY_BACKOFF_IN_SEC", default_value = "10")]
+    pub judge_retry_backoff_in_sec: u64,
+
+    /// Seconds a judge task may wait before it is served ahead of higher priority lanes
+    #[structopt(long, env = "QUEUE_STARVATION_TIMEOUT_IN_SEC", default_value = "180")]
+    pub queue_starvation_timeout_in_sec: u64,
+
+    /// How to pick a judge server for the next task
+    #[structopt(
+        long,
+        env = "SCHEDULING_STRATEGY",
+        default_value = "least_loaded",
+        possible_values = &["least_loaded", "cpu_weighted", "round_robin"]
+    )]
+    pub scheduling_strategy: String,
+
+    /// Where submissions are judged, on registered judge servers or on this host
+    #[structopt(
+        long,
+        env = "JUDGE_BACKEND",
+        default_value = "http",
+        possible_values = &["http", "local"]
+    )]
+    pub judge_backend: String,
+
+    /// User id the local judge backend runs programs as, only used when started as root
+    #[structopt(long, env = "LOCAL_JUDGE_UID", default_value = "65534")]
+    pub local_judge_uid: u32,
+
+    /// Group id the local judge backend runs programs as, only used when started as root
+    #[structopt(long, env = "LOCAL_JUDGE_GID", default_value = "65534")]
+    pub local_judge_gid: u32,
+
+    /// Bearer token Prometheus has to send to scrape /metrics,
+    /// the endpoint is disabled when it is not set
+    #[structopt(long, env = "METRICS_TOKEN")]
+    pub metrics_token: Option<String>,
+
     /// Domain
     #[structopt(long, env = "DOMAIN", default_value = "localhost")]
     pub domain: String,
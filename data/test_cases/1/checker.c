@@ -0,0 +1,42 @@
+#include <stdio.h>
+
+#define AC 0
+#define WA 1
+
+int check(FILE *input, FILE *user_output, FILE *answer);
+
+void close_file(FILE *f){
+    if(f != NULL){
+        fclose(f);
+    }
+}
+
+int main(int argc, char *args[]){
+    FILE *input = NULL, *user_output = NULL, *answer = NULL;
+    int result;
+    if(argc != 4){
+        fprintf(stderr, "Usage: checker x.in x.user_out x.out\n");
+        return 3;
+    }
+    input = fopen(args[1], "r");
+    user_output = fopen(args[2], "r");
+    answer = fopen(args[3], "r");
+    if(input == NULL || user_output == NULL || answer == NULL){
+        fprintf(stderr, "Failed to open files\n");
+        close_file(input);
+        close_file(user_output);
+        close_file(answer);
+        return 3;
+    }
+
+    result = check(input, user_output, answer);
+
+    close_file(input);
+    close_file(user_output);
+    close_file(answer);
+    return result;
+}
+
+int check(FILE *input, FILE *user_output, FILE *answer){
+    return AC;
+}
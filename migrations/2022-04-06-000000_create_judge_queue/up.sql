@@ -0,0 +1,7 @@
+CREATE TABLE judge_queue (
+    submission_id UUID PRIMARY KEY REFERENCES submissions (id) ON DELETE CASCADE,
+    enqueue_time TIMESTAMP NOT NULL,
+    lease_time TIMESTAMP
+);
+
+CREATE INDEX judge_queue_enqueue_time_idx ON judge_queue (enqueue_time);
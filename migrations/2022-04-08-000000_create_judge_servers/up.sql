@@ -0,0 +1,4 @@
+CREATE TABLE judge_servers (
+    service_url TEXT PRIMARY KEY,
+    state TEXT NOT NULL
+);
@@ -0,0 +1,9 @@
+CREATE TABLE rejudge_records (
+    batch_id UUID NOT NULL,
+    submission_id UUID NOT NULL REFERENCES submissions (id) ON DELETE CASCADE,
+    create_time TIMESTAMP NOT NULL,
+    old_is_accepted BOOLEAN,
+    old_err TEXT,
+    old_out_results TEXT[],
+    PRIMARY KEY (batch_id, submission_id)
+);
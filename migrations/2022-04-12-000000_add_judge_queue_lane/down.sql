@@ -0,0 +1,3 @@
+DROP INDEX judge_queue_lane_enqueue_time_idx;
+
+ALTER TABLE judge_queue DROP COLUMN lane;
@@ -0,0 +1,3 @@
+ALTER TABLE judge_queue ADD COLUMN lane INTEGER NOT NULL DEFAULT 1;
+
+CREATE INDEX judge_queue_lane_enqueue_time_idx ON judge_queue (lane, enqueue_time);
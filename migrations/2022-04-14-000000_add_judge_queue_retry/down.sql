@@ -0,0 +1,2 @@
+ALTER TABLE judge_queue DROP COLUMN retry_time;
+ALTER TABLE judge_queue DROP COLUMN attempts;
@@ -0,0 +1,2 @@
+ALTER TABLE judge_queue ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE judge_queue ADD COLUMN retry_time TIMESTAMP;
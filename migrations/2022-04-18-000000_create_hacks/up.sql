@@ -0,0 +1,17 @@
+CREATE TABLE hacks (
+    id UUID PRIMARY KEY,
+    submission_id UUID NOT NULL REFERENCES submissions (id) ON DELETE CASCADE,
+    region TEXT NOT NULL,
+    problem_id INTEGER NOT NULL,
+    hacker_id INTEGER NOT NULL,
+    defender_id INTEGER NOT NULL,
+    input TEXT NOT NULL,
+    answer TEXT,
+    state TEXT NOT NULL,
+    reason TEXT,
+    create_time TIMESTAMP NOT NULL,
+    finish_time TIMESTAMP,
+    test_case_id INTEGER
+);
+
+CREATE INDEX hacks_region_idx ON hacks (region);
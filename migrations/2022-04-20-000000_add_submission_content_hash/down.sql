@@ -0,0 +1,4 @@
+DROP INDEX submissions_content_hash_idx;
+
+ALTER TABLE submissions DROP COLUMN is_cached;
+ALTER TABLE submissions DROP COLUMN content_hash;
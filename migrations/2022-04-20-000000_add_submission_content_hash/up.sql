@@ -0,0 +1,4 @@
+ALTER TABLE submissions ADD COLUMN content_hash TEXT;
+ALTER TABLE submissions ADD COLUMN is_cached BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX submissions_content_hash_idx ON submissions (content_hash);
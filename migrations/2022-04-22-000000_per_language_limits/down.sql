@@ -0,0 +1,16 @@
+UPDATE problems SET settings = (
+    (settings::jsonb - 'max_cpu_time' - 'max_memory' - 'language_limits')
+    || jsonb_build_object(
+        'high_performance_max_cpu_time', settings::jsonb -> 'max_cpu_time',
+        'high_performance_max_memory', settings::jsonb -> 'max_memory',
+        'other_max_cpu_time', COALESCE(
+            settings::jsonb -> 'language_limits' -> 'py3' -> 'max_cpu_time',
+            settings::jsonb -> 'max_cpu_time'
+        ),
+        'other_max_memory', COALESCE(
+            settings::jsonb -> 'language_limits' -> 'py3' -> 'max_memory',
+            settings::jsonb -> 'max_memory'
+        )
+    )
+)::text
+WHERE settings::jsonb ? 'max_cpu_time';
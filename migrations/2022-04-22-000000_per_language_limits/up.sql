@@ -0,0 +1,21 @@
+-- High performance limits become the base limits, the other limits become
+-- absolute limits of the languages they used to apply to.
+UPDATE problems SET settings = (
+    (settings::jsonb
+        - 'high_performance_max_cpu_time'
+        - 'high_performance_max_memory'
+        - 'other_max_cpu_time'
+        - 'other_max_memory')
+    || jsonb_build_object(
+        'max_cpu_time', settings::jsonb -> 'high_performance_max_cpu_time',
+        'max_memory', settings::jsonb -> 'high_performance_max_memory',
+        'language_limits', (
+            SELECT jsonb_object_agg(language, jsonb_build_object(
+                'max_cpu_time', settings::jsonb -> 'other_max_cpu_time',
+                'max_memory', settings::jsonb -> 'other_max_memory'
+            ))
+            FROM unnest(ARRAY['java', 'kotlin', 'py2', 'py3']) AS language
+        )
+    )
+)::text
+WHERE settings::jsonb ? 'high_performance_max_cpu_time';
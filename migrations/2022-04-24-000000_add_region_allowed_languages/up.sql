@@ -0,0 +1,2 @@
+-- NULL accepts every enabled language
+ALTER TABLE regions ADD COLUMN allowed_languages TEXT[];
@@ -0,0 +1,136 @@
+use crate::judge_actor::JudgeActorAddr;
+use crate::models::users::LoggedUser;
+use crate::services::hack;
+use actix_web::{get, post, web, HttpResponse};
+use server_core::database::Pool;
+use server_core::errors::ServiceError;
+use uuid::Uuid;
+
+#[derive(Deserialize)]
+pub struct CreateHackBody {
+    submission_id: Uuid,
+    input: String,
+}
+
+#[post("")]
+pub async fn create(
+    body: web::Json<CreateHackBody>,
+    pool: web::Data<Pool>,
+    logged_user: LoggedUser,
+    judge_actor: web::Data<JudgeActorAddr>,
+) -> Result<HttpResponse, ServiceError> {
+    if logged_user.0.is_none() {
+        return Err(ServiceError::Unauthorized);
+    }
+
+    let res = web::block(move || {
+        hack::create(
+            body.submission_id,
+            body.input.clone(),
+            logged_user.0.unwrap().id,
+            pool,
+            judge_actor,
+        )
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("{}", e);
+        e
+    })?;
+
+    Ok(HttpResponse::Ok().json(res))
+}
+
+#[derive(Deserialize)]
+pub struct GetHackListParams {
+    region_filter: Option<String>,
+    problem_id_filter: Option<i32>,
+    hacker_id_filter: Option<i32>,
+    defender_id_filter: Option<i32>,
+    limit: i32,
+    offset: i32,
+}
+
+#[get("")]
+pub async fn get_list(
+    query: web::Query<GetHackListParams>,
+    logged_user: LoggedUser,
+    pool: web::Data<Pool>,
+) -> Result<HttpResponse, ServiceError> {
+    if logged_user.0.is_none() {
+        return Err(ServiceError::Unauthorized);
+    }
+
+    let res = web::block(move || {
+        hack::get_list(
+            query.region_filter.clone(),
+            query.problem_id_filter,
+            query.hacker_id_filter,
+            query.defender_id_filter,
+            query.limit,
+            query.offset,
+            pool,
+        )
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("{}", e);
+        e
+    })?;
+
+    Ok(HttpResponse::Ok().json(&res))
+}
+
+// The input is only shown to both sides of the hack.
+#[get("/{id}")]
+pub async fn get(
+    web::Path(id): web::Path<Uuid>,
+    logged_user: LoggedUser,
+    pool: web::Data<Pool>,
+) -> Result<HttpResponse, ServiceError> {
+    if logged_user.0.is_none() {
+        return Err(ServiceError::Unauthorized);
+    }
+    let cur_user = logged_user.0.unwrap();
+
+    let res = web::block(move || hack::get(id, pool)).await.map_err(|e| {
+        eprintln!("{}", e);
+        e
+    })?;
+
+    if cur_user.id != res.hacker_id
+        && cur_user.id != res.defender_id
+        && cur_user.role != "sup"
+        && cur_user.role != "admin"
+    {
+        let hint = "No permission.".to_string();
+        return Err(ServiceError::BadRequest(hint));
+    }
+
+    Ok(HttpResponse::Ok().json(&res))
+}
+
+#[post("/{id}/test_case")]
+pub async fn append_to_test_cases(
+    web::Path(id): web::Path<Uuid>,
+    logged_user: LoggedUser,
+    pool: web::Data<Pool>,
+) -> Result<HttpResponse, ServiceError> {
+    if logged_user.0.is_none() {
+        return Err(ServiceError::Unauthorized);
+    }
+    let cur_user = logged_user.0.unwrap();
+    if cur_user.role != "sup" && cur_user.role != "admin" {
+        let hint = "No permission.".to_string();
+        return Err(ServiceError::BadRequest(hint));
+    }
+
+    let res = web::block(move || hack::append_to_test_cases(id, pool))
+        .await
+        .map_err(|e| {
+            eprintln!("{}", e);
+            e
+        })?;
+
+    Ok(HttpResponse::Ok().json(res))
+}
@@ -0,0 +1,13 @@
+pub mod handler;
+
+use actix_web::web;
+
+pub fn route(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/hacks")
+            .service(handler::create)
+            .service(handler::get_list)
+            .service(handler::append_to_test_cases)
+            .service(handler::get),
+    );
+}
@@ -1,19 +1,12 @@
 use crate::judge_actor::JudgeActorAddr;
+use crate::models::judge_servers;
+use crate::models::users::LoggedUser;
 use crate::services::judge_server::*;
 use actix_identity::Identity;
 use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
+use server_core::database::Pool;
 use server_core::errors::ServiceError;
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct HeartbeatBody {
-    pub judger_version: String,
-    pub hostname: String,
-    pub cpu_core: i32,
-    pub memory: f32,
-    pub cpu: f32,
-    pub service_url: Option<String>,
-}
-
 #[derive(Debug, Clone, Serialize)]
 struct HeartbeatResponse {
     data: String,
@@ -22,29 +15,35 @@ struct HeartbeatResponse {
 
 #[post("/heartbeat")]
 pub async fn handle_heartbeat(
-    body: web::Json<HeartbeatBody>,
+    raw_body: web::Bytes,
     req: HttpRequest,
     judge_actor: web::Data<JudgeActorAddr>,
+    pool: web::Data<Pool>,
 ) -> Result<HttpResponse, ServiceError> {
-    let token = req
+    let token = match req
         .headers()
         .get("x-judge-server-token")
-        .unwrap()
-        .to_str()
-        .unwrap()
-        .to_string();
-
-    record_server_info(
-        body.judger_version.clone(),
-        body.hostname.clone(),
-        body.cpu_core,
-        body.memory,
-        body.cpu,
-        body.service_url.clone(),
-        token.clone(),
-        judge_actor,
-    )
-    .await?;
+        .and_then(|token| token.to_str().ok())
+    {
+        Some(token) => token.to_string(),
+        None => {
+            let hint = "Missing judge server token.".to_string();
+            return Err(ServiceError::UnauthorizedWithHint(hint));
+        }
+    };
+    let signature = req
+        .headers()
+        .get("x-judge-server-signature")
+        .and_then(|signature| signature.to_str().ok());
+
+    check_heartbeat(&token, signature, &raw_body)?;
+
+    let body: judge_servers::HeartbeatBody = serde_json::from_slice(&raw_body).map_err(|_| {
+        let hint = "Invalid heartbeat body.".to_string();
+        ServiceError::BadRequest(hint)
+    })?;
+
+    record_server_info(body, token.clone(), judge_actor, pool).await?;
 
     Ok(HttpResponse::Ok()
         .set_header("X-Judge-Server-Token", token)
@@ -61,3 +60,120 @@ pub async fn get_server_info(id: Identity) -> Result<HttpResponse, ServiceError>
         .await
         .map(|res| HttpResponse::Ok().json(&res))
 }
+
+#[get("/queue")]
+pub async fn get_queue_depth(
+    logged_user: LoggedUser,
+    pool: web::Data<Pool>,
+) -> Result<HttpResponse, ServiceError> {
+    if logged_user.0.is_none() {
+        return Err(ServiceError::Unauthorized);
+    }
+
+    let res = web::block(move || queue_depth(pool)).await.map_err(|e| {
+        eprintln!("{}", e);
+        e
+    })?;
+
+    Ok(HttpResponse::Ok().json(&res))
+}
+
+#[derive(Deserialize)]
+pub struct JudgeServerBody {
+    service_url: String,
+}
+
+async fn change_state(
+    state: &'static str,
+    body: web::Json<JudgeServerBody>,
+    logged_user: LoggedUser,
+    pool: web::Data<Pool>,
+    judge_actor: web::Data<JudgeActorAddr>,
+) -> Result<HttpResponse, ServiceError> {
+    if logged_user.0.is_none() {
+        return Err(ServiceError::Unauthorized);
+    }
+    let cur_user = logged_user.0.unwrap();
+    if cur_user.role != "sup" && cur_user.role != "admin" {
+        let hint = "No permission.".to_string();
+        return Err(ServiceError::BadRequest(hint));
+    }
+
+    web::block(move || set_state(body.service_url.clone(), state, pool, judge_actor))
+        .await
+        .map_err(|e| {
+            eprintln!("{}", e);
+            e
+        })?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[post("/drain")]
+pub async fn drain(
+    body: web::Json<JudgeServerBody>,
+    logged_user: LoggedUser,
+    pool: web::Data<Pool>,
+    judge_actor: web::Data<JudgeActorAddr>,
+) -> Result<HttpResponse, ServiceError> {
+    change_state(
+        judge_servers::DRAINING,
+        body,
+        logged_user,
+        pool,
+        judge_actor,
+    )
+    .await
+}
+
+#[post("/disable")]
+pub async fn disable(
+    body: web::Json<JudgeServerBody>,
+    logged_user: LoggedUser,
+    pool: web::Data<Pool>,
+    judge_actor: web::Data<JudgeActorAddr>,
+) -> Result<HttpResponse, ServiceError> {
+    change_state(
+        judge_servers::DISABLED,
+        body,
+        logged_user,
+        pool,
+        judge_actor,
+    )
+    .await
+}
+
+#[post("/enable")]
+pub async fn enable(
+    body: web::Json<JudgeServerBody>,
+    logged_user: LoggedUser,
+    pool: web::Data<Pool>,
+    judge_actor: web::Data<JudgeActorAddr>,
+) -> Result<HttpResponse, ServiceError> {
+    change_state(judge_servers::ACTIVE, body, logged_user, pool, judge_actor).await
+}
+
+#[post("/forget")]
+pub async fn forget_server(
+    body: web::Json<JudgeServerBody>,
+    logged_user: LoggedUser,
+    pool: web::Data<Pool>,
+) -> Result<HttpResponse, ServiceError> {
+    if logged_user.0.is_none() {
+        return Err(ServiceError::Unauthorized);
+    }
+    let cur_user = logged_user.0.unwrap();
+    if cur_user.role != "sup" && cur_user.role != "admin" {
+        let hint = "No permission.".to_string();
+        return Err(ServiceError::BadRequest(hint));
+    }
+
+    web::block(move || forget(body.service_url.clone(), pool))
+        .await
+        .map_err(|e| {
+            eprintln!("{}", e);
+            e
+        })?;
+
+    Ok(HttpResponse::Ok().finish())
+}
@@ -6,6 +6,11 @@ pub fn route(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/judge_servers")
             .service(handler::handle_heartbeat)
-            .service(handler::get_server_info),
+            .service(handler::get_server_info)
+            .service(handler::get_queue_depth)
+            .service(handler::drain)
+            .service(handler::disable)
+            .service(handler::enable)
+            .service(handler::forget_server),
     );
 }
@@ -0,0 +1,33 @@
+use crate::models::users::LoggedUser;
+use crate::services::language;
+use actix_web::{get, post, web, HttpResponse};
+use server_core::errors::ServiceError;
+
+#[get("")]
+pub async fn get_list() -> Result<HttpResponse, ServiceError> {
+    let res = web::block(language::get_list).await.map_err(|e| {
+        eprintln!("{}", e);
+        e
+    })?;
+
+    Ok(HttpResponse::Ok().json(&res))
+}
+
+#[post("/reload")]
+pub async fn reload(logged_user: LoggedUser) -> Result<HttpResponse, ServiceError> {
+    if logged_user.0.is_none() {
+        return Err(ServiceError::Unauthorized);
+    }
+    let cur_user = logged_user.0.unwrap();
+    if cur_user.role != "sup" && cur_user.role != "admin" {
+        let hint = "No permission.".to_string();
+        return Err(ServiceError::BadRequest(hint));
+    }
+
+    let res = web::block(language::reload).await.map_err(|e| {
+        eprintln!("{}", e);
+        e
+    })?;
+
+    Ok(HttpResponse::Ok().json(&res))
+}
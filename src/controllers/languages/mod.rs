@@ -0,0 +1,11 @@
+pub mod handler;
+
+use actix_web::web;
+
+pub fn route(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/languages")
+            .service(handler::get_list)
+            .service(handler::reload),
+    );
+}
@@ -0,0 +1,29 @@
+use crate::services::metrics::*;
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use server_core::database::Pool;
+use server_core::errors::ServiceError;
+
+#[derive(Debug, Clone)]
+pub struct MetricsToken(pub Option<String>);
+
+#[get("/metrics")]
+pub async fn get_metrics(
+    req: HttpRequest,
+    token: web::Data<MetricsToken>,
+    pool: web::Data<Pool>,
+) -> Result<HttpResponse, ServiceError> {
+    let authorization = req
+        .headers()
+        .get("authorization")
+        .and_then(|authorization| authorization.to_str().ok());
+    check_token(token.0.as_deref(), authorization)?;
+
+    let res = web::block(move || render(pool)).await.map_err(|e| {
+        eprintln!("{}", e);
+        e
+    })?;
+
+    Ok(HttpResponse::Ok()
+        .set_header("Content-Type", "text/plain; version=0.0.4")
+        .body(res))
+}
@@ -0,0 +1,7 @@
+pub mod handler;
+
+use actix_web::web;
+
+pub fn route(cfg: &mut web::ServiceConfig) {
+    cfg.service(handler::get_metrics);
+}
@@ -1,8 +1,12 @@
 pub mod contests;
+pub mod hacks;
 pub mod judge_servers;
+pub mod languages;
+pub mod metrics;
 pub mod problem_sets;
 pub mod problems;
 pub mod regions;
+pub mod rejudges;
 pub mod samples;
 pub mod submissions;
 pub mod users;
@@ -85,6 +85,7 @@ pub async fn get_linked_problem_column_list(
     logged_user: LoggedUser,
 ) -> Result<HttpResponse, ServiceError> {
     check_view_right(pool.clone(), logged_user.clone(), region.clone())?;
+    let user_id = logged_user.0.map(|user| user.id);
 
     let res = web::block(move || {
         region::get_linked_problem_column_list(
@@ -99,6 +100,7 @@ pub async fn get_linked_problem_column_list(
             query.difficulty_order.clone(),
             query.limit,
             query.offset,
+            user_id,
             pool,
         )
     })
@@ -165,6 +167,40 @@ pub async fn create_submission(
     Ok(HttpResponse::Ok().json(&res))
 }
 
+#[derive(Deserialize)]
+pub struct SetAllowedLanguagesBody {
+    allowed_languages: Option<Vec<String>>,
+}
+
+#[put("/{region}/languages")]
+pub async fn set_allowed_languages(
+    web::Path(region): web::Path<String>,
+    body: web::Json<SetAllowedLanguagesBody>,
+    pool: web::Data<Pool>,
+    logged_user: LoggedUser,
+) -> Result<HttpResponse, ServiceError> {
+    info!("{:?}", logged_user.0);
+    if logged_user.0.is_none() {
+        return Err(ServiceError::Unauthorized);
+    }
+    let cur_user = logged_user.0.unwrap();
+    if cur_user.role != "sup" && cur_user.role != "admin" {
+        let hint = "No permission.".to_string();
+        return Err(ServiceError::BadRequest(hint));
+    }
+
+    let res = web::block(move || {
+        region::set_allowed_languages(region, body.allowed_languages.clone(), pool)
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("{}", e);
+        e
+    })?;
+
+    Ok(HttpResponse::Ok().json(&res))
+}
+
 #[delete("/{region}/{inner_id}")]
 pub async fn delete_problem(
     web::Path((region, inner_id)): web::Path<(String, i32)>,
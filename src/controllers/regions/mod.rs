@@ -10,6 +10,7 @@ pub fn route(cfg: &mut web::ServiceConfig) {
             .service(handler::get_linked_problem_column_list)
             .service(handler::get_linked_problem)
             .service(handler::create_submission)
+            .service(handler::set_allowed_languages)
             .service(handler::delete_problem),
     );
 }
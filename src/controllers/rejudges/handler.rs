@@ -0,0 +1,73 @@
+use crate::judge_actor::JudgeActorAddr;
+use crate::models::users::LoggedUser;
+use crate::services::rejudge;
+use actix_web::{get, post, web, HttpResponse};
+use server_core::database::Pool;
+use server_core::errors::ServiceError;
+use uuid::Uuid;
+
+#[derive(Deserialize)]
+pub struct CreateRejudgeBody {
+    submission_id: Option<Uuid>,
+    problem_id: Option<i32>,
+    region: Option<String>,
+}
+
+#[post("")]
+pub async fn create(
+    body: web::Json<CreateRejudgeBody>,
+    logged_user: LoggedUser,
+    pool: web::Data<Pool>,
+    judge_actor: web::Data<JudgeActorAddr>,
+) -> Result<HttpResponse, ServiceError> {
+    if logged_user.0.is_none() {
+        return Err(ServiceError::Unauthorized);
+    }
+    let cur_user = logged_user.0.unwrap();
+    if cur_user.role != "sup" && cur_user.role != "admin" {
+        let hint = "No permission.".to_string();
+        return Err(ServiceError::BadRequest(hint));
+    }
+
+    let res = web::block(move || {
+        rejudge::create(
+            body.submission_id,
+            body.problem_id,
+            body.region.clone(),
+            pool,
+            judge_actor,
+        )
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("{}", e);
+        e
+    })?;
+
+    Ok(HttpResponse::Ok().json(&res))
+}
+
+#[get("/{batch_id}")]
+pub async fn get_report(
+    web::Path(batch_id): web::Path<Uuid>,
+    logged_user: LoggedUser,
+    pool: web::Data<Pool>,
+) -> Result<HttpResponse, ServiceError> {
+    if logged_user.0.is_none() {
+        return Err(ServiceError::Unauthorized);
+    }
+    let cur_user = logged_user.0.unwrap();
+    if cur_user.role != "sup" && cur_user.role != "admin" {
+        let hint = "No permission.".to_string();
+        return Err(ServiceError::BadRequest(hint));
+    }
+
+    let res = web::block(move || rejudge::get_report(batch_id, pool))
+        .await
+        .map_err(|e| {
+            eprintln!("{}", e);
+            e
+        })?;
+
+    Ok(HttpResponse::Ok().json(&res))
+}
@@ -0,0 +1,11 @@
+pub mod handler;
+
+use actix_web::web;
+
+pub fn route(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/rejudges")
+            .service(handler::create)
+            .service(handler::get_report),
+    );
+}
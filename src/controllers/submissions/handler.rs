@@ -1,7 +1,9 @@
-use crate::judge_actor::JudgeActorAddr;
+use crate::judge_actor::{handler::RunCode, JudgeActorAddr};
+use crate::models::submissions::RunResult;
 use crate::models::users::LoggedUser;
 use crate::services::submission;
 use actix_web::{get, post, put, web, HttpResponse};
+use futures::StreamExt;
 use server_core::database::{db_connection, Pool};
 use server_core::errors::ServiceError;
 use uuid::Uuid;
@@ -46,6 +48,116 @@ pub async fn create(
     Ok(HttpResponse::Ok().json(&res))
 }
 
+#[derive(Deserialize)]
+pub struct RunBody {
+    problem_id: i32,
+    src: String,
+    language: String,
+    input: String,
+}
+
+#[post("/run")]
+pub async fn run(
+    body: web::Json<RunBody>,
+    pool: web::Data<Pool>,
+    logged_user: LoggedUser,
+    judge_actor: web::Data<JudgeActorAddr>,
+) -> Result<HttpResponse, ServiceError> {
+    if logged_user.0.is_none() {
+        return Err(ServiceError::Unauthorized);
+    }
+    let cur_user = logged_user.0.unwrap();
+    let _running_user = submission::start_run(cur_user.id)?;
+
+    let body = body.into_inner();
+    let settings = web::block(move || {
+        submission::build_run_settings(body.problem_id, body.src, body.language, body.input, pool)
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("{}", e);
+        e
+    })?;
+
+    let judge_result = judge_actor
+        .addr
+        .send(RunCode(settings))
+        .await
+        .map_err(|e| ServiceError::InternalServerErrorWithHint(e.to_string()))??;
+
+    Ok(HttpResponse::Ok().json(RunResult::from(judge_result)))
+}
+
+#[derive(Deserialize)]
+pub struct SubscribeParams {
+    ids: String,
+}
+
+#[get("/events")]
+pub async fn subscribe(
+    query: web::Query<SubscribeParams>,
+    logged_user: LoggedUser,
+    pool: web::Data<Pool>,
+) -> Result<HttpResponse, ServiceError> {
+    if logged_user.0.is_none() {
+        return Err(ServiceError::Unauthorized);
+    }
+    let cur_user = logged_user.0.unwrap();
+
+    let mut submission_ids = Vec::new();
+    for id in query.ids.split(',') {
+        submission_ids.push(Uuid::parse_str(id.trim()).map_err(|_| {
+            let hint = "Invalid submission id.".to_string();
+            ServiceError::BadRequest(hint)
+        })?);
+    }
+    submission_ids.sort();
+    submission_ids.dedup();
+    if submission_ids.len() > submission::events::MAX_SUBSCRIBED_SUBMISSIONS {
+        let hint = format!(
+            "Can not subscribe to more than {} submissions.",
+            submission::events::MAX_SUBSCRIBED_SUBMISSIONS
+        );
+        return Err(ServiceError::BadRequest(hint));
+    }
+
+    let owner_ids = {
+        let submission_ids = submission_ids.clone();
+        let pool = pool.clone();
+        web::block(move || submission::events::owners(submission_ids, pool))
+            .await
+            .map_err(|e| {
+                eprintln!("{}", e);
+                e
+            })?
+    };
+    for owner_id in owner_ids {
+        if cur_user.id != owner_id && cur_user.role != "sup" && cur_user.role != "admin" {
+            let hint = "No permission.".to_string();
+            return Err(ServiceError::BadRequest(hint));
+        }
+    }
+
+    let receiver = web::block(move || submission::events::subscribe(submission_ids, pool))
+        .await
+        .map_err(|e| {
+            eprintln!("{}", e);
+            e
+        })?;
+
+    let stream = receiver.map(|event| {
+        Ok::<_, actix_web::Error>(web::Bytes::from(format!(
+            "data: {}\n\n",
+            serde_json::to_string(&event).unwrap()
+        )))
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .streaming(stream))
+}
+
 #[get("/{id}")]
 pub async fn get(
     web::Path(submission_id): web::Path<Uuid>,
@@ -119,3 +231,34 @@ pub async fn get_list(
 
     Ok(HttpResponse::Ok().json(&res))
 }
+
+#[derive(Deserialize)]
+pub struct GetFailedListParams {
+    limit: i32,
+    offset: i32,
+}
+
+#[get("/failed")]
+pub async fn get_failed_list(
+    query: web::Query<GetFailedListParams>,
+    logged_user: LoggedUser,
+    pool: web::Data<Pool>,
+) -> Result<HttpResponse, ServiceError> {
+    if logged_user.0.is_none() {
+        return Err(ServiceError::Unauthorized);
+    }
+    let cur_user = logged_user.0.unwrap();
+    if cur_user.role != "sup" && cur_user.role != "admin" {
+        let hint = "No permission.".to_string();
+        return Err(ServiceError::BadRequest(hint));
+    }
+
+    let res = web::block(move || submission::get_failed_list(query.limit, query.offset, pool))
+        .await
+        .map_err(|e| {
+            eprintln!("{}", e);
+            e
+        })?;
+
+    Ok(HttpResponse::Ok().json(&res))
+}
@@ -6,6 +6,9 @@ pub fn route(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/submissions")
             .service(handler::create)
+            .service(handler::run)
+            .service(handler::get_failed_list)
+            .service(handler::subscribe)
             .service(handler::get)
             .service(handler::get_list),
     );
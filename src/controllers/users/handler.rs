@@ -273,3 +273,35 @@ pub async fn get_submissions_time(
 
     Ok(HttpResponse::Ok().json(&res))
 }
+
+#[derive(Deserialize)]
+pub struct GetBestScoresParams {
+    region: Option<String>,
+}
+
+#[get("/{id}/best_scores")]
+pub async fn get_best_scores(
+    web::Path(user_id): web::Path<i32>,
+    query: web::Query<GetBestScoresParams>,
+    logged_user: LoggedUser,
+    pool: web::Data<Pool>,
+) -> Result<HttpResponse, ServiceError> {
+    if logged_user.0.is_none() {
+        return Err(ServiceError::Unauthorized);
+    }
+    // scores of contests with access settings are not public, so only the owner sees them
+    let cur_user = logged_user.0.unwrap();
+    if cur_user.id != user_id && cur_user.role != "sup" && cur_user.role != "admin" {
+        let hint = "No permission.".to_string();
+        return Err(ServiceError::BadRequest(hint));
+    }
+
+    let res = web::block(move || user::get_best_scores(user_id, query.region.clone(), pool))
+        .await
+        .map_err(|e| {
+            eprintln!("{}", e);
+            e
+        })?;
+
+    Ok(HttpResponse::Ok().json(&res))
+}
@@ -0,0 +1,104 @@
+use super::{JudgeBackend, JudgeBackendError};
+use crate::judge_actor::checker;
+use crate::judge_actor::client::{run_judge_client, JudgeClientError};
+use crate::judge_actor::compare::{expected_outputs, recheck};
+use crate::judge_actor::scheduling::SchedulingStrategy;
+use crate::judge_actor::utils::choose_judge_server;
+use crate::models::problems::TEST_CASE_ROOT;
+use crate::models::submissions::{JudgeResult, JudgeSettings};
+use crate::statics::JUDGE_SERVER_INFOS;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+// QingdaoU JudgeServer instances registered through heartbeats.
+pub struct HttpBackend {
+    scheduler: Arc<dyn SchedulingStrategy>,
+}
+
+impl HttpBackend {
+    pub fn new(scheduler: Arc<dyn SchedulingStrategy>) -> Self {
+        HttpBackend { scheduler }
+    }
+}
+
+fn change_task_number(service_url: &str, delta: i32) {
+    let mut lock = JUDGE_SERVER_INFOS.write().unwrap();
+    if let Some(server_info) = lock.get_mut(service_url) {
+        server_info.task_number += delta;
+    }
+}
+
+impl JudgeBackend for HttpBackend {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    fn choose_target(&self) -> Option<String> {
+        choose_judge_server(self.scheduler.as_ref()).map(|(service_url, _)| service_url)
+    }
+
+    fn judge(
+        &self,
+        target: &str,
+        settings: &JudgeSettings,
+        timeout: Duration,
+    ) -> Result<JudgeResult, JudgeBackendError> {
+        // QingdaoU JudgeServer has no interactors, sending them would judge without one
+        if settings.interactor_config.is_some() {
+            let hint = "interactive problems need the local judge backend".to_owned();
+            return Err(JudgeBackendError::Unsupported(hint));
+        }
+
+        // the server may have been forgotten since it was chosen
+        let token = match JUDGE_SERVER_INFOS.read().unwrap().get(target) {
+            Some(server_info) => server_info.token.clone(),
+            None => {
+                let hint = format!("{} is no longer registered", target);
+                return Err(JudgeClientError::ConnectionRefused(hint).into());
+            }
+        };
+
+        // judge servers take either of them, test_case_id is only kept to find the checker
+        let mut server_settings = settings.clone();
+        if server_settings.test_case.is_some() {
+            server_settings.test_case_id = None;
+        }
+        server_settings.checker = None;
+
+        if settings.comparator.is_none() && settings.checker.is_none() {
+            change_task_number(target, 1);
+            let judge_result = run_judge_client(target, &token, &server_settings, timeout);
+            change_task_number(target, -1);
+            return Ok(judge_result?);
+        }
+
+        // outputs are needed to compare or check them here
+        let test_case_root = Path::new(TEST_CASE_ROOT);
+        let expected_outputs = match settings.comparator {
+            Some(_) => expected_outputs(settings, test_case_root)?,
+            None => HashMap::new(),
+        };
+        let mut output_settings = server_settings;
+        output_settings.output = true;
+
+        change_task_number(target, 1);
+        let judge_result = run_judge_client(target, &token, &output_settings, timeout);
+        change_task_number(target, -1);
+
+        let mut result = judge_result?;
+        if let Some(comparator) = settings.comparator {
+            recheck(&mut result, &comparator, &expected_outputs);
+        }
+        if let Some(checker) = settings.checker.as_ref() {
+            checker::recheck(&mut result, checker, settings, test_case_root)?;
+        }
+        if !settings.output {
+            for detail in result.details.iter_mut().flatten() {
+                detail.output = None;
+            }
+        }
+        Ok(result)
+    }
+}
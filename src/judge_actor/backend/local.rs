@@ -0,0 +1,709 @@
+use super::{JudgeBackend, JudgeBackendError};
+use crate::judge_actor::checker::{checker_timeout, run_checker, Verdict};
+use crate::judge_actor::compare::is_match;
+use crate::models::submissions::{
+    JudgeResult, JudgeSettings, RawJudgeResult, RawJudgeResultData, TestCase, COMPILE_ERROR_ERR,
+};
+use digest::Digest;
+use hex::ToHex;
+use md5::Md5;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+// result codes of QingdaoU Judger, see JudgeResultData::from
+const WRONG_ANSWER: i32 = -1;
+const SUCCESS: i32 = 0;
+const CPU_TIME_LIMIT_EXCEEDED: i32 = 1;
+const REAL_TIME_LIMIT_EXCEEDED: i32 = 2;
+const MEMORY_LIMIT_EXCEEDED: i32 = 3;
+const RUNTIME_ERROR: i32 = 4;
+const SYSTEM_ERROR: i32 = 5;
+const SPJ_ERROR: i32 = -11;
+
+const MAX_OUTPUT_SIZE: u64 = 64 * 1024 * 1024;
+// processes and threads of the sandbox user, shared by everything judged at the same time
+const MAX_PROCESSES: u64 = 256;
+const MAX_COMPILE_MESSAGE_SIZE: usize = 64 * 1024;
+
+// Judge on this host, for small deployments and CI without judge server images.
+// Programs run in a temp dir under rlimits in their own process group, as a separate user
+// when started as root. Answers are kept from that user by permissions only, inline ones
+// by those of the work dir and test data by those of `test_case_dir`, set here.
+pub struct LocalBackend {
+    test_case_dir: PathBuf,
+    run_as: Option<(u32, u32)>,
+}
+
+impl LocalBackend {
+    pub fn new(test_case_dir: &str, uid: u32, gid: u32) -> Self {
+        let run_as = if unsafe { libc::geteuid() } == 0 {
+            // directories of problems may be created readable by anyone
+            let restricted = fs::create_dir_all(test_case_dir).and_then(|_| {
+                fs::set_permissions(test_case_dir, fs::Permissions::from_mode(0o700))
+            });
+            if let Err(e) = restricted {
+                panic!("Can not keep {} from judged programs: {}", test_case_dir, e);
+            }
+            Some((uid, gid))
+        } else {
+            log::warn!("Not running as root, local judge runs programs as the current user.");
+            None
+        };
+
+        LocalBackend {
+            test_case_dir: PathBuf::from(test_case_dir),
+            run_as,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct TestCaseInfo {
+    input_name: String,
+    output_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct TestCasesInfo {
+    test_cases: BTreeMap<String, TestCaseInfo>,
+}
+
+#[derive(Clone, Copy)]
+struct Limits {
+    cpu_time: i32,
+    real_time: i32,
+    memory: i64,
+    memory_limit_check_only: bool,
+}
+
+struct RunStat {
+    cpu_time: i32,
+    real_time: i32,
+    memory: i32,
+    signal: i32,
+    exit_code: i32,
+    is_killed: bool,
+}
+
+// Source and compile settings of an interactor.
+struct Helper<'a> {
+    src: &'a str,
+    src_name: &'a str,
+    exe_name: &'a str,
+    compile_command: &'a str,
+    limits: Limits,
+}
+
+// Removes the working directory whatever way judging ends.
+struct WorkDir(PathBuf);
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.0).unwrap_or(());
+    }
+}
+
+fn render(template: &str, vars: &[(&str, &str)]) -> String {
+    let mut res = template.to_owned();
+    for (name, value) in vars {
+        res = res.replace(&format!("{{{}}}", name), value);
+    }
+    res
+}
+
+fn md5_hex(content: &[u8]) -> String {
+    let mut hasher = Md5::new();
+    hasher.update(content);
+    hasher.finalize().encode_hex::<String>()
+}
+
+fn set_rlimit(resource: libc::__rlimit_resource_t, limit: u64) -> io::Result<()> {
+    let rlimit = libc::rlimit {
+        rlim_cur: limit as libc::rlim_t,
+        rlim_max: limit as libc::rlim_t,
+    };
+    if unsafe { libc::setrlimit(resource, &rlimit) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// Wait for `pid` and kill its process group once it runs longer than the real time limit.
+// Whatever it left running in the background is killed once it exits.
+fn wait(pid: libc::pid_t, start_time: Instant, limits: &Limits) -> io::Result<RunStat> {
+    let (done_sender, done_receiver) = mpsc::channel::<()>();
+    let real_time = Duration::from_millis(limits.real_time.max(0) as u64)
+        .checked_sub(start_time.elapsed())
+        .unwrap_or_default();
+    let watchdog = thread::spawn(move || {
+        if let Err(RecvTimeoutError::Timeout) = done_receiver.recv_timeout(real_time) {
+            unsafe { libc::kill(-pid, libc::SIGKILL) };
+            return true;
+        }
+        false
+    });
+
+    // the exited process is left unreaped, so its pid can not name another group yet
+    let ret = loop {
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        let ret = unsafe {
+            libc::waitid(
+                libc::P_PID,
+                pid as libc::id_t,
+                &mut info,
+                libc::WEXITED | libc::WNOWAIT,
+            )
+        };
+        if ret < 0 && io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+            continue;
+        }
+        break ret;
+    };
+    let real_time = start_time.elapsed().as_millis() as i32;
+    done_sender.send(()).unwrap_or(());
+    let is_killed = watchdog.join().unwrap_or(false);
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    unsafe { libc::kill(-pid, libc::SIGKILL) };
+
+    // wait4 instead of Child::wait, for the resource usage of this very process
+    let mut status = 0;
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::wait4(pid, &mut status, 0, &mut usage) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let cpu_time = (usage.ru_utime.tv_sec + usage.ru_stime.tv_sec) * 1000
+        + (usage.ru_utime.tv_usec + usage.ru_stime.tv_usec) / 1000;
+    Ok(RunStat {
+        cpu_time: cpu_time as i32,
+        real_time,
+        memory: (usage.ru_maxrss * 1024).min(i32::MAX as i64) as i32,
+        signal: if libc::WIFSIGNALED(status) {
+            libc::WTERMSIG(status)
+        } else {
+            0
+        },
+        exit_code: if libc::WIFEXITED(status) {
+            libc::WEXITSTATUS(status)
+        } else {
+            0
+        },
+        is_killed,
+    })
+}
+
+impl LocalBackend {
+    // A command with env, rlimits and user set in a process group of its own,
+    // stdio is left to the caller. Trusted helpers are not run as the sandbox user.
+    fn command(
+        &self,
+        command: &str,
+        env: &[String],
+        work_dir: &Path,
+        limits: &Limits,
+        is_trusted: bool,
+    ) -> io::Result<Command> {
+        let args: Vec<&str> = command.split_whitespace().collect();
+        if args.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "empty command"));
+        }
+
+        let mut cmd = Command::new(args[0]);
+        cmd.args(&args[1..])
+            .current_dir(work_dir)
+            .env_clear()
+            .env("PATH", std::env::var("PATH").unwrap_or_default());
+        for pair in env {
+            if let Some((name, value)) = pair.split_once('=') {
+                cmd.env(name, value);
+            }
+        }
+        let run_as = if is_trusted { None } else { self.run_as };
+        if let Some((uid, gid)) = run_as {
+            cmd.uid(uid).gid(gid);
+        }
+        cmd.process_group(0);
+
+        let cpu_seconds = (limits.cpu_time.max(0) as u64).div_ceil(1000) + 1;
+        let memory = if limits.memory > 0 && !limits.memory_limit_check_only {
+            // address space is always bigger than the resident memory checked below
+            Some(limits.memory as u64 * 2)
+        } else {
+            None
+        };
+        unsafe {
+            cmd.pre_exec(move || {
+                set_rlimit(libc::RLIMIT_CPU, cpu_seconds)?;
+                set_rlimit(libc::RLIMIT_FSIZE, MAX_OUTPUT_SIZE)?;
+                set_rlimit(libc::RLIMIT_CORE, 0)?;
+                // counted per user, so only bounded for the sandbox user
+                if run_as.is_some() {
+                    set_rlimit(libc::RLIMIT_NPROC, MAX_PROCESSES)?;
+                }
+                if let Some(memory) = memory {
+                    set_rlimit(libc::RLIMIT_AS, memory)?;
+                    set_rlimit(libc::RLIMIT_STACK, memory)?;
+                }
+                Ok(())
+            });
+        }
+
+        Ok(cmd)
+    }
+
+    fn run(
+        &self,
+        command: &str,
+        env: &[String],
+        work_dir: &Path,
+        stdin: Option<&Path>,
+        stdout: &Path,
+        limits: &Limits,
+    ) -> io::Result<RunStat> {
+        let mut cmd = self.command(command, env, work_dir, limits, false)?;
+        cmd.stdout(File::create(stdout)?)
+            .stderr(File::create(stdout.with_extension("err"))?);
+        match stdin {
+            Some(stdin) => cmd.stdin(File::open(stdin)?),
+            None => cmd.stdin(Stdio::null()),
+        };
+
+        let start_time = Instant::now();
+        let child = cmd.spawn()?;
+        wait(child.id() as libc::pid_t, start_time, limits)
+    }
+
+    // Run the program with the interactor reading its stdout and writing its stdin,
+    // returns the stats of the program and of the interactor.
+    // Interactors are compiled from the problem, so they are trusted to read the answers.
+    fn run_interactive(
+        &self,
+        command: &str,
+        env: &[String],
+        interactor_command: &str,
+        work_dir: &Path,
+        limits: &Limits,
+        interactor_limits: &Limits,
+    ) -> io::Result<(RunStat, RunStat)> {
+        let start_time = Instant::now();
+        let mut interactor = {
+            let mut cmd =
+                self.command(interactor_command, &[], work_dir, interactor_limits, true)?;
+            cmd.stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(File::create(work_dir.join("interactor.err"))?);
+            cmd.spawn()?
+        };
+        let interactor_pid = interactor.id() as libc::pid_t;
+
+        // the pipe ends are moved into the command and closed here once it is dropped,
+        // so either side sees EOF when the other one exits
+        let child = {
+            let mut cmd = self.command(command, env, work_dir, limits, false)?;
+            cmd.stdin(Stdio::from(interactor.stdout.take().unwrap()))
+                .stdout(Stdio::from(interactor.stdin.take().unwrap()))
+                .stderr(Stdio::null());
+            cmd.spawn()
+        };
+        let child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                unsafe { libc::kill(-interactor_pid, libc::SIGKILL) };
+                wait(interactor_pid, start_time, interactor_limits)?;
+                return Err(e);
+            }
+        };
+
+        let interactor_limits = *interactor_limits;
+        let interactor_waiter =
+            thread::spawn(move || wait(interactor_pid, start_time, &interactor_limits));
+        let stat = wait(child.id() as libc::pid_t, start_time, limits)?;
+        let interactor_stat = interactor_waiter
+            .join()
+            .map_err(|_| io::Error::other("interactor waiter panicked"))??;
+
+        Ok((stat, interactor_stat))
+    }
+
+    // Compile into `work_dir`, the compiler message is returned on failure.
+    fn compile(
+        &self,
+        compile_command: &str,
+        env: &[String],
+        work_dir: &Path,
+        limits: &Limits,
+    ) -> io::Result<Result<(), String>> {
+        let stdout = work_dir.join("compile.out");
+        let stat = self.run(compile_command, env, work_dir, None, &stdout, limits)?;
+        if stat.exit_code == 0 && stat.signal == 0 && !stat.is_killed {
+            return Ok(Ok(()));
+        }
+
+        let mut message = fs::read_to_string(stdout.with_extension("err")).unwrap_or_default();
+        if message.trim().is_empty() {
+            message = fs::read_to_string(&stdout).unwrap_or_default();
+        }
+        if stat.is_killed {
+            message.push_str("\nCompilation timed out.");
+        }
+        let mut end = message.len().min(MAX_COMPILE_MESSAGE_SIZE);
+        while !message.is_char_boundary(end) {
+            end -= 1;
+        }
+        message.truncate(end);
+        Ok(Err(message))
+    }
+
+    // Compile an interactor, returns the path of the executable.
+    fn compile_helper(
+        &self,
+        helper: &Helper,
+        spj_version: &str,
+        test_case_dir: &Path,
+        work_dir: &Path,
+    ) -> io::Result<Result<String, String>> {
+        let version = [("spj_version", spj_version)];
+        let src_path = work_dir.join(render(helper.src_name, &version));
+        let exe_path = work_dir.join(render(helper.exe_name, &version));
+        let src_path_string = src_path.to_string_lossy().to_string();
+        let exe_path_string = exe_path.to_string_lossy().to_string();
+        let work_path = work_dir.to_string_lossy().to_string();
+        let test_case_path = test_case_dir.to_string_lossy().to_string();
+        fs::write(&src_path, helper.src)?;
+
+        let compile_command = render(
+            helper.compile_command,
+            &[
+                ("src_path", &src_path_string),
+                ("exe_dir", &work_path),
+                ("exe_path", &exe_path_string),
+                ("test_case_dir", &test_case_path),
+            ],
+        );
+        Ok(self
+            .compile(&compile_command, &[], work_dir, &helper.limits)?
+            .map(|_| exe_path_string))
+    }
+
+    // Input and expected output paths of every test case, inline test cases are written
+    // to `data_dir`, which the sandbox user can not read.
+    fn test_case_paths(
+        &self,
+        settings: &JudgeSettings,
+        data_dir: &Path,
+    ) -> Result<Vec<(String, PathBuf, Option<PathBuf>)>, JudgeBackendError> {
+        let mut res = Vec::new();
+        if let Some(test_cases) = settings.test_case.as_ref() {
+            for (index, TestCase { input, output }) in test_cases.iter().enumerate() {
+                let id = (index + 1).to_string();
+                let input_path = data_dir.join(format!("{}.in", id));
+                let output_path = data_dir.join(format!("{}.out", id));
+                fs::write(&input_path, input)?;
+                fs::write(&output_path, output)?;
+                res.push((id, input_path, Some(output_path)));
+            }
+            return Ok(res);
+        }
+
+        let test_case_id = settings
+            .test_case_id
+            .clone()
+            .ok_or_else(|| JudgeBackendError::Sandbox("No test case given.".to_owned()))?;
+        let test_case_dir = self.test_case_dir.join(test_case_id);
+        let info: TestCasesInfo =
+            serde_json::from_str(&fs::read_to_string(test_case_dir.join("info"))?)
+                .map_err(|e| JudgeBackendError::Sandbox(e.to_string()))?;
+
+        let mut test_cases: Vec<(String, TestCaseInfo)> = info.test_cases.into_iter().collect();
+        test_cases.sort_by_key(|(id, _)| id.parse::<i32>().unwrap_or(0));
+        for (id, test_case) in test_cases {
+            res.push((
+                id,
+                test_case_dir.join(test_case.input_name),
+                test_case
+                    .output_name
+                    .map(|output_name| test_case_dir.join(output_name)),
+            ));
+        }
+        Ok(res)
+    }
+}
+
+impl JudgeBackend for LocalBackend {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    // concurrency is bounded by the count of judge actors
+    fn choose_target(&self) -> Option<String> {
+        Some("local".to_owned())
+    }
+
+    fn judge(
+        &self,
+        _target: &str,
+        settings: &JudgeSettings,
+        timeout: Duration,
+    ) -> Result<JudgeResult, JudgeBackendError> {
+        let started = Instant::now();
+        let work_dir =
+            WorkDir(std::env::temp_dir().join(format!("shupdtp-judge-{}", Uuid::new_v4())));
+        fs::create_dir_all(&work_dir.0)?;
+        if let Some((uid, gid)) = self.run_as {
+            let path = std::ffi::CString::new(work_dir.0.to_string_lossy().as_bytes())
+                .map_err(|e| JudgeBackendError::Sandbox(e.to_string()))?;
+            if unsafe { libc::chown(path.as_ptr(), uid, gid) } != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+        }
+        let work_path = work_dir.0.to_string_lossy().to_string();
+        // stdin of programs is opened here, only trusted helpers read from this directory
+        let data_dir = WorkDir(work_dir.0.with_extension("data"));
+        fs::create_dir_all(&data_dir.0)?;
+        fs::set_permissions(&data_dir.0, fs::Permissions::from_mode(0o700))?;
+
+        // compile the submission
+        let language_config = &settings.language_config;
+        let src_path = work_dir.0.join(&language_config.compile.src_name);
+        let exe_path = work_dir.0.join(&language_config.compile.exe_name);
+        let src_path_string = src_path.to_string_lossy().to_string();
+        let exe_path_string = exe_path.to_string_lossy().to_string();
+        fs::write(&src_path, &settings.src)?;
+
+        let compile_command = render(
+            &language_config.compile.compile_command,
+            &[
+                ("src_path", &src_path_string),
+                ("exe_dir", &work_path),
+                ("exe_path", &exe_path_string),
+            ],
+        );
+        let compile_limits = Limits {
+            cpu_time: language_config.compile.max_cpu_time,
+            real_time: language_config.compile.max_real_time,
+            memory: language_config.compile.max_memory as i64,
+            memory_limit_check_only: false,
+        };
+        if let Err(message) = self.compile(
+            &compile_command,
+            &language_config.run.env,
+            &work_dir.0,
+            &compile_limits,
+        )? {
+            return Ok(JudgeResult::from(RawJudgeResult {
+                err: Some(COMPILE_ERROR_ERR.to_owned()),
+                data: serde_json::Value::String(message),
+            }));
+        }
+
+        // compile the interactor, checkers are compiled at import
+        let spj_version = settings.spj_version.clone().unwrap_or_default();
+        let test_case_dir = settings
+            .test_case_id
+            .as_ref()
+            .map(|test_case_id| self.test_case_dir.join(test_case_id))
+            .unwrap_or_else(|| work_dir.0.clone());
+        let mut interactor_exe_path = None;
+        if let (Some(interactor_config), Some(interactor_compile_config), Some(interactor_src)) = (
+            settings.interactor_config.as_ref(),
+            settings.interactor_compile_config.as_ref(),
+            settings.interactor_src.as_ref(),
+        ) {
+            let helper = Helper {
+                src: interactor_src,
+                src_name: &interactor_compile_config.src_name,
+                exe_name: &interactor_compile_config.exe_name,
+                compile_command: &interactor_compile_config.compile_command,
+                limits: Limits {
+                    cpu_time: interactor_compile_config.max_cpu_time,
+                    real_time: interactor_compile_config.max_real_time,
+                    memory: interactor_compile_config.max_memory as i64,
+                    memory_limit_check_only: false,
+                },
+            };
+            match self.compile_helper(&helper, &spj_version, &test_case_dir, &work_dir.0)? {
+                Ok(exe_path) => {
+                    interactor_exe_path = Some((interactor_config.command.clone(), exe_path))
+                }
+                Err(message) => {
+                    return Ok(JudgeResult::from(RawJudgeResult {
+                        err: Some("InteractorCompileError".to_owned()),
+                        data: serde_json::Value::String(message),
+                    }));
+                }
+            }
+        }
+
+        // run every test case
+        let run_command = render(
+            &language_config.run.command,
+            &[
+                ("exe_path", &exe_path_string),
+                ("exe_dir", &work_path),
+                ("max_memory", &(settings.max_memory / 1024).to_string()),
+            ],
+        );
+        let run_limits = Limits {
+            cpu_time: settings.max_cpu_time,
+            real_time: settings.max_cpu_time * 3,
+            memory: settings.max_memory as i64,
+            memory_limit_check_only: language_config.run.memory_limit_check_only != 0,
+        };
+
+        let mut data = Vec::new();
+        for (id, input_path, output_path) in self.test_case_paths(settings, &data_dir.0)? {
+            if started.elapsed() > timeout {
+                return Err(JudgeBackendError::Sandbox("Judge timed out.".to_owned()));
+            }
+
+            // for interactive problems this is the output file the interactor writes
+            let user_output_path = work_dir.0.join(format!("{}.out", id));
+            let mut interactor_result = None;
+            let stat = match interactor_exe_path.as_ref() {
+                Some((interactor_command, interactor_exe_path)) => {
+                    let input_path_string = input_path.to_string_lossy().to_string();
+                    let user_output_path_string = user_output_path.to_string_lossy().to_string();
+                    let answer_path_string = output_path
+                        .as_ref()
+                        .map(|output_path| output_path.to_string_lossy().to_string())
+                        .unwrap_or_else(|| "/dev/null".to_owned());
+                    let interactor_command = render(
+                        interactor_command,
+                        &[
+                            ("exe_path", interactor_exe_path),
+                            ("in_file_path", &input_path_string),
+                            ("user_out_file_path", &user_output_path_string),
+                            ("answer_file_path", &answer_path_string),
+                        ],
+                    );
+                    let (stat, interactor_stat) = self.run_interactive(
+                        &run_command,
+                        &language_config.run.env,
+                        &interactor_command,
+                        &work_dir.0,
+                        &run_limits,
+                        &Limits {
+                            cpu_time: settings.max_cpu_time * 3,
+                            real_time: settings.max_cpu_time * 9,
+                            memory: -1,
+                            memory_limit_check_only: true,
+                        },
+                    )?;
+                    // testlib exit codes, 1 is a wrong answer and 2 a presentation error,
+                    // an interactor killed by SIGPIPE wrote to a program which already exited
+                    interactor_result = Some(
+                        match (
+                            interactor_stat.signal,
+                            interactor_stat.exit_code,
+                            interactor_stat.is_killed,
+                        ) {
+                            (0, 0, false) => SUCCESS,
+                            (0, 1, false) | (0, 2, false) => WRONG_ANSWER,
+                            (libc::SIGPIPE, _, false) => WRONG_ANSWER,
+                            _ => SYSTEM_ERROR,
+                        },
+                    );
+                    stat
+                }
+                None => self.run(
+                    &run_command,
+                    &language_config.run.env,
+                    &work_dir.0,
+                    Some(&input_path),
+                    &user_output_path,
+                    &run_limits,
+                )?,
+            };
+            let user_output = fs::read(&user_output_path).unwrap_or_default();
+
+            let mut error = 0;
+            let result = if stat.is_killed {
+                REAL_TIME_LIMIT_EXCEEDED
+            } else if stat.cpu_time > settings.max_cpu_time || stat.signal == libc::SIGXCPU {
+                CPU_TIME_LIMIT_EXCEEDED
+            } else if settings.max_memory > 0 && stat.memory > settings.max_memory {
+                MEMORY_LIMIT_EXCEEDED
+            } else if interactor_result == Some(WRONG_ANSWER) {
+                // the program is likely killed by SIGPIPE after the interactor gave up
+                WRONG_ANSWER
+            } else if stat.signal != 0 || stat.exit_code != 0 {
+                RUNTIME_ERROR
+            } else if let Some(interactor_result) = interactor_result {
+                if interactor_result == SYSTEM_ERROR {
+                    error = SPJ_ERROR;
+                }
+                interactor_result
+            } else if let Some(checker) = settings.checker.as_ref() {
+                let verdict = run_checker(
+                    checker,
+                    &test_case_dir,
+                    &input_path,
+                    &user_output_path,
+                    output_path
+                        .as_deref()
+                        .unwrap_or_else(|| Path::new("/dev/null")),
+                    checker_timeout(settings),
+                )?;
+                match verdict {
+                    Verdict::Accepted => SUCCESS,
+                    Verdict::WrongAnswer => WRONG_ANSWER,
+                    Verdict::Failed => {
+                        error = SPJ_ERROR;
+                        SYSTEM_ERROR
+                    }
+                }
+            } else {
+                let expected_output = match output_path {
+                    Some(output_path) => fs::read(output_path)?,
+                    None => Vec::new(),
+                };
+                let comparator = settings.comparator.unwrap_or_default();
+                if is_match(&comparator, &user_output, &expected_output) {
+                    SUCCESS
+                } else {
+                    WRONG_ANSWER
+                }
+            };
+
+            data.push(RawJudgeResultData {
+                cpu_time: stat.cpu_time,
+                real_time: stat.real_time,
+                memory: stat.memory,
+                signal: stat.signal,
+                exit_code: stat.exit_code,
+                error,
+                result,
+                test_case: id,
+                output_md5: Some(md5_hex(&user_output)),
+                output: if settings.output {
+                    Some(String::from_utf8_lossy(&user_output).to_string())
+                } else {
+                    None
+                },
+                stderr: if settings.output {
+                    fs::read(user_output_path.with_extension("err"))
+                        .ok()
+                        .map(|stderr| String::from_utf8_lossy(&stderr).to_string())
+                } else {
+                    None
+                },
+            });
+        }
+
+        Ok(JudgeResult::from(RawJudgeResult {
+            err: None,
+            data: serde_json::to_value(data).unwrap(),
+        }))
+    }
+}
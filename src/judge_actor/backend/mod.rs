@@ -0,0 +1,65 @@
+mod http;
+mod local;
+
+pub use http::HttpBackend;
+pub use local::LocalBackend;
+
+use super::client::JudgeClientError;
+use super::scheduling;
+use crate::models::problems::TEST_CASE_ROOT;
+use crate::models::submissions::{JudgeResult, JudgeSettings};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum JudgeBackendError {
+    #[error(transparent)]
+    Client(#[from] JudgeClientError),
+
+    #[error("Sandbox error: {0}")]
+    Sandbox(String),
+
+    #[error("{0} was quarantined after failing too often")]
+    Quarantined(String),
+
+    // the settings need what this backend does not have, the target is not to blame
+    #[error("Not supported by the judge backend: {0}")]
+    Unsupported(String),
+}
+
+impl From<std::io::Error> for JudgeBackendError {
+    fn from(error: std::io::Error) -> JudgeBackendError {
+        JudgeBackendError::Sandbox(error.to_string())
+    }
+}
+
+// Something able to run the JudgeSettings built from a submission and its problem.
+pub trait JudgeBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // Where the next task is going to run, None when there is no capacity left.
+    fn choose_target(&self) -> Option<String>;
+
+    fn judge(
+        &self,
+        target: &str,
+        settings: &JudgeSettings,
+        timeout: Duration,
+    ) -> Result<JudgeResult, JudgeBackendError>;
+}
+
+pub fn from_opt(opt: &server_core::cli_args::Opt) -> Arc<dyn JudgeBackend> {
+    match opt.judge_backend.as_str() {
+        "local" => Arc::new(LocalBackend::new(
+            TEST_CASE_ROOT,
+            opt.local_judge_uid,
+            opt.local_judge_gid,
+        )),
+        _ => {
+            let scheduler = scheduling::from_name(&opt.scheduling_strategy);
+            info!("Scheduling judge servers by {}", scheduler.name());
+            Arc::new(HttpBackend::new(scheduler))
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use crate::models::languages::CheckerConfig;
+use crate::models::submissions::{JudgeResult, JudgeResultData, JudgeSettings};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verdict {
+    Accepted,
+    WrongAnswer,
+    Failed,
+}
+
+// Checkers are compiled and validated at import, so they are trusted
+// and run on this host without a sandbox, only under a timeout.
+// Testlib exit codes, 1 is a wrong answer and 2 a presentation error.
+pub fn run_checker(
+    checker: &CheckerConfig,
+    test_case_dir: &Path,
+    input_path: &Path,
+    user_output_path: &Path,
+    answer_path: &Path,
+    timeout: Duration,
+) -> io::Result<Verdict> {
+    let command = checker
+        .command
+        .replace("{exe_dir}", &test_case_dir.to_string_lossy())
+        .replace("{in_file_path}", &input_path.to_string_lossy())
+        .replace("{user_out_file_path}", &user_output_path.to_string_lossy())
+        .replace("{answer_file_path}", &answer_path.to_string_lossy());
+    let args: Vec<&str> = command.split_whitespace().collect();
+    if args.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "empty checker command",
+        ));
+    }
+    let mut child = Command::new(args[0])
+        .args(&args[1..])
+        .current_dir(test_case_dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    let started = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(match status.code() {
+                Some(0) => Verdict::Accepted,
+                Some(1) | Some(2) => Verdict::WrongAnswer,
+                _ => Verdict::Failed,
+            });
+        }
+        if started.elapsed() > timeout {
+            child.kill().unwrap_or(());
+            child.wait()?;
+            return Ok(Verdict::Failed);
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+pub fn checker_timeout(settings: &JudgeSettings) -> Duration {
+    Duration::from_millis(settings.max_cpu_time.max(1000) as u64 * 9)
+}
+
+#[derive(Deserialize)]
+struct TestCasePaths {
+    input_name: String,
+    output_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TestCasesPaths {
+    test_cases: HashMap<String, TestCasePaths>,
+}
+
+// Input and answer paths by test case id, inline test cases are written to `work_dir`.
+fn test_case_paths(
+    settings: &JudgeSettings,
+    test_case_dir: &Path,
+    work_dir: &Path,
+) -> io::Result<HashMap<String, (PathBuf, PathBuf)>> {
+    let mut res = HashMap::new();
+    if let Some(test_cases) = settings.test_case.as_ref() {
+        for (index, test_case) in test_cases.iter().enumerate() {
+            let id = (index + 1).to_string();
+            let input_path = work_dir.join(format!("{}.in", id));
+            let answer_path = work_dir.join(format!("{}.ans", id));
+            fs::write(&input_path, &test_case.input)?;
+            fs::write(&answer_path, &test_case.output)?;
+            res.insert(id, (input_path, answer_path));
+        }
+        return Ok(res);
+    }
+
+    let info: TestCasesPaths =
+        serde_json::from_str(&fs::read_to_string(test_case_dir.join("info"))?)?;
+    for (id, paths) in info.test_cases {
+        let answer_path = match paths.output_name {
+            Some(output_name) => test_case_dir.join(output_name),
+            None => PathBuf::from("/dev/null"),
+        };
+        res.insert(id, (test_case_dir.join(paths.input_name), answer_path));
+    }
+    Ok(res)
+}
+
+// Run the checker on outputs sent back by a judge server,
+// which only compares output md5 on its own.
+// The checker is found in the directory of test_case_id even for inline test cases.
+pub fn recheck(
+    result: &mut JudgeResult,
+    checker: &CheckerConfig,
+    settings: &JudgeSettings,
+    test_case_root: &Path,
+) -> io::Result<()> {
+    let test_case_id = match settings.test_case_id.as_ref() {
+        Some(test_case_id) => test_case_id,
+        None => return Ok(()),
+    };
+    let details = match result.details.as_mut() {
+        Some(details) => details,
+        None => return Ok(()),
+    };
+
+    let test_case_dir = test_case_root.join(test_case_id);
+    let work_dir = std::env::temp_dir().join(format!("shupdtp-checker-{}", Uuid::new_v4()));
+    fs::create_dir_all(&work_dir)?;
+    let res = recheck_details(details, checker, settings, &test_case_dir, &work_dir);
+    fs::remove_dir_all(&work_dir).unwrap_or(());
+    res?;
+
+    result.is_accepted = Some(details.iter().all(|detail| detail.result == "SUCCESS"));
+    Ok(())
+}
+
+fn recheck_details(
+    details: &mut [JudgeResultData],
+    checker: &CheckerConfig,
+    settings: &JudgeSettings,
+    test_case_dir: &Path,
+    work_dir: &Path,
+) -> io::Result<()> {
+    let paths = test_case_paths(settings, test_case_dir, work_dir)?;
+    let user_output_path = work_dir.join("user.out");
+    for detail in details.iter_mut() {
+        if detail.result != "SUCCESS" && detail.result != "WRONG_ANSWER" {
+            continue;
+        }
+        let (input_path, answer_path) = match paths.get(&detail.test_case) {
+            Some(paths) => paths,
+            None => continue,
+        };
+        fs::write(&user_output_path, detail.output.clone().unwrap_or_default())?;
+
+        let verdict = run_checker(
+            checker,
+            test_case_dir,
+            input_path,
+            &user_output_path,
+            answer_path,
+            checker_timeout(settings),
+        )?;
+        match verdict {
+            Verdict::Accepted => detail.result = "SUCCESS".to_owned(),
+            Verdict::WrongAnswer => detail.result = "WRONG_ANSWER".to_owned(),
+            Verdict::Failed => {
+                detail.result = "SYSTEM_ERROR".to_owned();
+                detail.error = "SPJ_ERROR".to_owned();
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use crate::models::submissions::{JudgeResult, JudgeSettings, RawJudgeResult, RawJudgeResultData};
+use actix_web::client::{Client, ConnectError, SendRequestError};
+use actix_web::rt::System;
+use std::time::Duration;
+use thiserror::Error;
+
+// judge results carry user output when `output` is set, so allow large bodies
+const MAX_RESPONSE_SIZE: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Error)]
+pub enum JudgeClientError {
+    #[error("Connection refused: {0}")]
+    ConnectionRefused(String),
+
+    #[error("Timeout while waiting for judge server")]
+    Timeout,
+
+    #[error("HTTP error: {0}")]
+    HttpError(String),
+
+    #[error("Malformed JSON: {0}")]
+    MalformedJson(String),
+}
+
+impl From<SendRequestError> for JudgeClientError {
+    fn from(error: SendRequestError) -> JudgeClientError {
+        match error {
+            SendRequestError::Timeout | SendRequestError::Connect(ConnectError::Timeout) => {
+                JudgeClientError::Timeout
+            }
+            SendRequestError::Connect(e) => JudgeClientError::ConnectionRefused(e.to_string()),
+            e => JudgeClientError::HttpError(e.to_string()),
+        }
+    }
+}
+
+pub async fn send_judge_request(
+    url: &str,
+    token: &str,
+    settings: &JudgeSettings,
+    timeout: Duration,
+) -> Result<JudgeResult, JudgeClientError> {
+    let mut response = Client::new()
+        .post(format!("{}/judge", url))
+        .set_header("X-Judge-Server-Token", token)
+        .set_header("Content-Type", "application/json")
+        .timeout(timeout)
+        .send_json(settings)
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(JudgeClientError::HttpError(format!(
+            "judge server responded with {}",
+            response.status()
+        )));
+    }
+
+    let body = response
+        .body()
+        .limit(MAX_RESPONSE_SIZE)
+        .await
+        .map_err(|e| JudgeClientError::HttpError(e.to_string()))?;
+
+    let raw_result = serde_json::from_slice::<RawJudgeResult>(&body)
+        .map_err(|e| JudgeClientError::MalformedJson(e.to_string()))?;
+
+    // check the shape of `data` here, so converting to JudgeResult can not panic
+    if raw_result.err.is_none() {
+        serde_json::from_value::<Vec<RawJudgeResultData>>(raw_result.data.clone())
+            .map_err(|e| JudgeClientError::MalformedJson(e.to_string()))?;
+    } else {
+        serde_json::from_value::<String>(raw_result.data.clone())
+            .map_err(|e| JudgeClientError::MalformedJson(e.to_string()))?;
+    }
+
+    Ok(JudgeResult::from(raw_result))
+}
+
+// JudgeActor runs in a SyncArbiter thread which has no event loop,
+// so every request gets its own short-lived System to run on.
+pub fn run_judge_client(
+    url: &str,
+    token: &str,
+    settings: &JudgeSettings,
+    timeout: Duration,
+) -> Result<JudgeResult, JudgeClientError> {
+    let url = url.to_owned();
+    let token = token.to_owned();
+    let settings = settings.clone();
+
+    System::new("judge-client")
+        .block_on(async move { send_judge_request(&url, &token, &settings, timeout).await })
+}
@@ -0,0 +1,176 @@
+use crate::models::problems::Comparator;
+use crate::models::submissions::{JudgeResult, JudgeSettings};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+fn lines_without_trailing_whitespace(output: &str) -> Vec<&str> {
+    let mut lines: Vec<&str> = output.split('\n').map(|line| line.trim_end()).collect();
+    while lines.last() == Some(&"") {
+        lines.pop();
+    }
+    lines
+}
+
+fn is_close(user: f64, expected: f64, abs_eps: f64, rel_eps: f64) -> bool {
+    if expected.is_nan() || user.is_nan() {
+        return expected.is_nan() && user.is_nan();
+    }
+    if user == expected {
+        return true;
+    }
+    let diff = (user - expected).abs();
+    diff <= abs_eps || diff <= rel_eps * expected.abs()
+}
+
+pub fn is_match(comparator: &Comparator, user_bytes: &[u8], expected_bytes: &[u8]) -> bool {
+    let user = String::from_utf8_lossy(user_bytes);
+    let expected = String::from_utf8_lossy(expected_bytes);
+    match *comparator {
+        Comparator::Exact => user_bytes == expected_bytes,
+        Comparator::Stripped => user.trim_end() == expected.trim_end(),
+        Comparator::TrailingWhitespace => {
+            lines_without_trailing_whitespace(&user) == lines_without_trailing_whitespace(&expected)
+        }
+        Comparator::AllWhitespace => user
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .eq(expected.chars().filter(|c| !c.is_whitespace())),
+        Comparator::CaseInsensitiveTokens => {
+            let user: Vec<String> = user.split_whitespace().map(str::to_lowercase).collect();
+            let expected: Vec<String> =
+                expected.split_whitespace().map(str::to_lowercase).collect();
+            user == expected
+        }
+        Comparator::Float { abs_eps, rel_eps } => {
+            let user: Vec<&str> = user.split_whitespace().collect();
+            let expected: Vec<&str> = expected.split_whitespace().collect();
+            user.len() == expected.len()
+                && user.iter().zip(expected.iter()).all(|(user, expected)| {
+                    match (user.parse::<f64>(), expected.parse::<f64>()) {
+                        (Ok(user), Ok(expected)) => is_close(user, expected, abs_eps, rel_eps),
+                        _ => user == expected,
+                    }
+                })
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TestCaseOutput {
+    output_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TestCaseOutputs {
+    test_cases: HashMap<String, TestCaseOutput>,
+}
+
+// Expected outputs by test case id, inline test cases are numbered from 1.
+pub fn expected_outputs(
+    settings: &JudgeSettings,
+    test_case_root: &Path,
+) -> io::Result<HashMap<String, Vec<u8>>> {
+    let mut res = HashMap::new();
+    if let Some(test_cases) = settings.test_case.as_ref() {
+        for (index, test_case) in test_cases.iter().enumerate() {
+            res.insert(
+                (index + 1).to_string(),
+                test_case.output.clone().into_bytes(),
+            );
+        }
+        return Ok(res);
+    }
+
+    if let Some(test_case_id) = settings.test_case_id.as_ref() {
+        let test_case_dir = test_case_root.join(test_case_id);
+        let info: TestCaseOutputs =
+            serde_json::from_str(&fs::read_to_string(test_case_dir.join("info"))?)?;
+        for (id, test_case) in info.test_cases {
+            if let Some(output_name) = test_case.output_name {
+                res.insert(id, fs::read(test_case_dir.join(output_name))?);
+            }
+        }
+    }
+    Ok(res)
+}
+
+// Judge servers only compare md5 of the stripped output, so check the returned outputs again.
+// Only answers the program finished normally with are touched.
+pub fn recheck(
+    result: &mut JudgeResult,
+    comparator: &Comparator,
+    expected_outputs: &HashMap<String, Vec<u8>>,
+) {
+    let details = match result.details.as_mut() {
+        Some(details) => details,
+        None => return,
+    };
+
+    for detail in details.iter_mut() {
+        if detail.result != "SUCCESS" && detail.result != "WRONG_ANSWER" {
+            continue;
+        }
+        let user_output = detail.output.clone().unwrap_or_default();
+        let expected_output = expected_outputs
+            .get(&detail.test_case)
+            .map(|output| output.as_slice())
+            .unwrap_or_default();
+        detail.result = if is_match(comparator, user_output.as_bytes(), expected_output) {
+            "SUCCESS".to_owned()
+        } else {
+            "WRONG_ANSWER".to_owned()
+        };
+    }
+
+    result.is_accepted = Some(details.iter().all(|detail| detail.result == "SUCCESS"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(comparator: Comparator, user: &str, expected: &str) -> bool {
+        is_match(&comparator, user.as_bytes(), expected.as_bytes())
+    }
+
+    #[test]
+    fn whitespace_modes() {
+        assert!(check(Comparator::Stripped, "1 2\n\n", "1 2"));
+        assert!(!check(Comparator::Stripped, "1 2 \n3", "1 2\n3"));
+        assert!(!check(Comparator::Exact, "1 2\n", "1 2"));
+        assert!(check(
+            Comparator::TrailingWhitespace,
+            "1 2 \r\n3\t\n\n",
+            "1 2\n3"
+        ));
+        assert!(!check(Comparator::TrailingWhitespace, "1  2\n3", "1 2\n3"));
+        assert!(check(Comparator::AllWhitespace, "1 2\n 3", "12 3"));
+        assert!(check(
+            Comparator::CaseInsensitiveTokens,
+            "YES\n  no",
+            "yes No\n"
+        ));
+        assert!(!check(Comparator::CaseInsensitiveTokens, "yes", "yes no"));
+    }
+
+    #[test]
+    fn float_tokens() {
+        let comparator = Comparator::Float {
+            abs_eps: 1e-6,
+            rel_eps: 0.0,
+        };
+        assert!(check(comparator, "0.3333333 x\n", "0.33333333 x"));
+        assert!(!check(comparator, "0.3334", "0.3333"));
+        assert!(!check(comparator, "0.3333333 y", "0.33333333 x"));
+        assert!(!check(comparator, "nan", "1.0"));
+
+        let comparator = Comparator::Float {
+            abs_eps: 0.0,
+            rel_eps: 1e-6,
+        };
+        assert!(check(comparator, "1000000.5", "1000000"));
+        assert!(!check(comparator, "1.5", "1"));
+    }
+}
@@ -0,0 +1,353 @@
+use super::backend::JudgeBackendError;
+use super::handler::notify;
+use super::JudgeActor;
+use crate::models::hacks::*;
+use crate::models::{languages, problems, submissions};
+use crate::services::problem::utils::validate_input;
+use crate::services::submission::build_settings;
+use crate::statics::{ACM_RANK_CACHE, RESULT_STATISTICS_CACHE};
+use actix::prelude::*;
+use diesel::prelude::*;
+use server_core::database::db_connection;
+use server_core::errors::{ServiceError, ServiceResult};
+use server_core::utils::time::get_cur_naive_date_time;
+use std::fs;
+use std::thread;
+use std::time::Duration;
+use uuid::Uuid;
+
+// the hack input shows up as this test case in the result of a hacked submission
+const HACK_TEST_CASE: &str = "hack";
+// hacks are not queued, so they are started again after this when no target is free
+const HACK_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StartHack(pub Uuid);
+
+impl Message for StartHack {
+    type Result = ();
+}
+
+// a hack the backend failed to judge, started again after a backoff
+struct RetryHack {
+    id: Uuid,
+    attempts: i32,
+}
+
+impl Message for RetryHack {
+    type Result = ();
+}
+
+struct Outcome {
+    state: &'static str,
+    reason: Option<String>,
+    answer: Option<String>,
+    // the failed run of the target, only for successful hacks
+    target_detail: Option<submissions::JudgeResultData>,
+}
+
+impl Outcome {
+    // Waiting when another attempt may do better.
+    fn of_backend_error(e: JudgeBackendError) -> Self {
+        match e {
+            JudgeBackendError::Unsupported(_) => Outcome::finished(FAILED, e.to_string()),
+            e => Outcome::finished(WAITING, e.to_string()),
+        }
+    }
+
+    fn finished(state: &'static str, reason: String) -> Self {
+        Outcome {
+            state,
+            reason: Some(reason),
+            answer: None,
+            target_detail: None,
+        }
+    }
+}
+
+impl Handler<StartHack> for JudgeActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: StartHack, ctx: &mut Self::Context) -> Self::Result {
+        self.start_hack(msg.0, 0, ctx);
+    }
+}
+
+impl Handler<RetryHack> for JudgeActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: RetryHack, ctx: &mut Self::Context) -> Self::Result {
+        self.start_hack(msg.id, msg.attempts, ctx);
+    }
+}
+
+impl JudgeActor {
+    // `attempts` is how many times the backend failed to judge it before.
+    fn start_hack(&self, id: Uuid, attempts: i32, ctx: &mut SyncContext<Self>) {
+        let conn = match db_connection(&self.pool) {
+            Ok(conn) => conn,
+            Err(_) => {
+                log::error!("Error connecting to database for hack {}.", id);
+                return;
+            }
+        };
+
+        let hack: Hack = {
+            use crate::schema::hacks as hacks_schema;
+            match hacks_schema::table
+                .filter(hacks_schema::id.eq(id))
+                .first(&conn)
+            {
+                Ok(hack) => hack,
+                Err(_) => {
+                    log::error!("Error loading hack {}.", id);
+                    return;
+                }
+            }
+        };
+        if hack.state != WAITING {
+            return;
+        }
+
+        // an actor must not wait here for a free target, queued submissions need it too
+        let judge_target = match self.backend.choose_target() {
+            Some(judge_target) => judge_target,
+            None => {
+                retry_later(ctx, id, attempts, HACK_RETRY_INTERVAL);
+                return;
+            }
+        };
+
+        let outcome = match self.judge_hack(&conn, &hack, &judge_target) {
+            Ok(outcome) if outcome.state == WAITING => {
+                let reason = outcome.reason.unwrap_or_default();
+                log::error!("Error judging hack {} on {}: {}", id, judge_target, reason);
+                // backoff doubles on every attempt, the same as for submissions
+                let attempts = attempts + 1;
+                if attempts < self.max_attempts {
+                    let backoff = self.retry_backoff * 2i32.pow((attempts - 1).min(10) as u32);
+                    retry_later(ctx, id, attempts, backoff.to_std().unwrap_or_default());
+                    return;
+                }
+                let reason = format!("Judge failed {} times: {}", attempts, reason);
+                Outcome::finished(FAILED, reason)
+            }
+            Ok(outcome) => outcome,
+            Err(e) => Outcome::finished(FAILED, e.to_string()),
+        };
+        info!("hack {} is {}", hack.id, outcome.state);
+        if finish(&conn, &hack, outcome).is_err() {
+            log::error!("Error saving the outcome of hack {}.", hack.id);
+            return;
+        }
+
+        notify(&conn, hack.submission_id);
+        RESULT_STATISTICS_CACHE
+            .write()
+            .unwrap()
+            .remove(&(hack.region.clone(), hack.problem_id));
+        ACM_RANK_CACHE.write().unwrap().remove(&hack.region);
+    }
+
+    // Validate the input, take the answer from the reference solution and run the target on it.
+    fn judge_hack(
+        &self,
+        conn: &PgConnection,
+        hack: &Hack,
+        judge_target: &str,
+    ) -> ServiceResult<Outcome> {
+        use crate::schema::problems as problems_schema;
+        use crate::schema::submissions as submissions_schema;
+
+        let raw_problem: problems::RawProblem = problems_schema::table
+            .filter(problems_schema::id.eq(hack.problem_id))
+            .first(conn)?;
+        let problem = problems::Problem::from(raw_problem);
+        let test_case_dir = problems::test_case_dir(problem.id);
+
+        if let Some(validator_language) = problem.settings.validator_language.as_ref() {
+            let input_path = std::env::temp_dir().join(format!("shupdtp-hack-{}.in", hack.id));
+            fs::write(&input_path, &hack.input)?;
+            let validated = validate_input(&test_case_dir, validator_language, &input_path);
+            fs::remove_file(&input_path).unwrap_or(());
+            if let Err(message) = validated? {
+                let reason = format!("Validator rejects the input: {}", message);
+                return Ok(Outcome::finished(INVALID_INPUT, reason));
+            }
+        }
+
+        let std_language = match problem.settings.std_language.as_ref() {
+            Some(std_language) => std_language,
+            None => {
+                let reason = "Problem has no reference solution.".to_owned();
+                return Ok(Outcome::finished(FAILED, reason));
+            }
+        };
+        let std_name = match languages::STD_SOURCES
+            .iter()
+            .find(|(_, language)| language == std_language)
+        {
+            Some((std_name, _)) => *std_name,
+            None => {
+                let reason = "Unknown reference language.".to_owned();
+                return Ok(Outcome::finished(FAILED, reason));
+            }
+        };
+        let std_src = fs::read_to_string(format!("{}/{}", test_case_dir, std_name))?;
+        let mut std_settings = build_settings(conn, problem.id, std_src, std_language)?;
+        std_settings.test_case_id = None;
+        std_settings.test_case = Some(vec![submissions::TestCase {
+            input: hack.input.clone(),
+            output: String::new(),
+        }]);
+        std_settings.checker = None;
+        std_settings.comparator = None;
+        std_settings.output = true;
+
+        let std_result = match self.judge_unqueued(judge_target, &std_settings) {
+            Ok(std_result) => std_result,
+            Err(e) => return Ok(Outcome::of_backend_error(e)),
+        };
+        let std_detail = std_result
+            .details
+            .and_then(|details| details.into_iter().next());
+        let answer = match std_detail {
+            // there is nothing to compare with, so a wrong answer means it ran fine
+            Some(detail) if detail.result == "SUCCESS" || detail.result == "WRONG_ANSWER" => {
+                detail.output.unwrap_or_default()
+            }
+            Some(detail) => {
+                let reason = format!("Reference solution got {} on the input.", detail.result);
+                return Ok(Outcome::finished(INVALID_INPUT, reason));
+            }
+            None => {
+                let reason = format!(
+                    "Reference solution failed: {}",
+                    std_result.err_reason.or(std_result.err).unwrap_or_default()
+                );
+                return Ok(Outcome::finished(FAILED, reason));
+            }
+        };
+
+        let raw_settings: String = submissions_schema::table
+            .filter(submissions_schema::id.eq(hack.submission_id))
+            .select(submissions_schema::settings)
+            .first(conn)?;
+        let mut target_settings: submissions::JudgeSettings = serde_json::from_str(&raw_settings)
+            .map_err(|_| {
+            let hint = format!("Broken settings in submission {}.", hack.submission_id);
+            ServiceError::InternalServerErrorWithHint(hint)
+        })?;
+        // test_case_id is kept, checkers are found in its directory
+        target_settings.test_case = Some(vec![submissions::TestCase {
+            input: hack.input.clone(),
+            output: answer.clone(),
+        }]);
+        target_settings.output = false;
+
+        let target_result = match self.judge_unqueued(judge_target, &target_settings) {
+            Ok(target_result) => target_result,
+            Err(e) => return Ok(Outcome::of_backend_error(e)),
+        };
+        let target_detail = target_result
+            .details
+            .and_then(|details| details.into_iter().next());
+        Ok(match target_detail {
+            Some(detail) if detail.result == "SUCCESS" => Outcome {
+                state: UNSUCCESSFUL,
+                reason: None,
+                answer: Some(answer),
+                target_detail: None,
+            },
+            Some(detail) => Outcome {
+                state: SUCCESSFUL,
+                reason: Some(detail.result.clone()),
+                answer: Some(answer),
+                target_detail: Some(detail),
+            },
+            None => {
+                let reason = format!(
+                    "Target failed to run: {}",
+                    target_result.err.unwrap_or_default()
+                );
+                Outcome::finished(FAILED, reason)
+            }
+        })
+    }
+}
+
+fn retry_later(ctx: &mut SyncContext<JudgeActor>, id: Uuid, attempts: i32, delay: Duration) {
+    let addr = ctx.address();
+    thread::spawn(move || {
+        thread::sleep(delay);
+        addr.do_send(RetryHack { id, attempts });
+    });
+}
+
+// Save the outcome, a successful hack turns the target into a failed submission with no score.
+fn finish(conn: &PgConnection, hack: &Hack, outcome: Outcome) -> ServiceResult<()> {
+    use crate::schema::hacks as hacks_schema;
+    use crate::schema::submissions as submissions_schema;
+
+    conn.transaction(|| {
+        diesel::update(hacks_schema::table.filter(hacks_schema::id.eq(hack.id)))
+            .set((
+                hacks_schema::state.eq(outcome.state.to_owned()),
+                hacks_schema::reason.eq(outcome.reason),
+                hacks_schema::answer.eq(outcome.answer),
+                hacks_schema::finish_time.eq(get_cur_naive_date_time()),
+            ))
+            .execute(conn)?;
+
+        let mut detail = match outcome.target_detail {
+            Some(detail) => detail,
+            None => return Ok(()),
+        };
+        let raw_result: Option<String> = submissions_schema::table
+            .filter(submissions_schema::id.eq(hack.submission_id))
+            .select(submissions_schema::result)
+            .first(conn)?;
+        let mut result = match raw_result.and_then(|raw_result| {
+            serde_json::from_str::<submissions::JudgeResult>(&raw_result).ok()
+        }) {
+            Some(result) => result,
+            None => {
+                let hint = format!("Submission {} has no result.", hack.submission_id);
+                return Err(ServiceError::InternalServerErrorWithHint(hint));
+            }
+        };
+        detail.test_case = HACK_TEST_CASE.to_owned();
+        detail.output = None;
+        detail.stderr = None;
+        result.details.get_or_insert_with(Vec::new).push(detail);
+        result.is_accepted = Some(false);
+
+        let out_results: Option<Vec<String>> = result
+            .out_results()
+            .map(|result_set| result_set.into_iter().collect());
+        diesel::update(
+            submissions_schema::table.filter(submissions_schema::id.eq(hack.submission_id)),
+        )
+        .set((
+            submissions_schema::result.eq(serde_json::to_string(&result).unwrap()),
+            submissions_schema::is_accepted.eq(Some(false)),
+            submissions_schema::out_results.eq(out_results),
+            submissions_schema::score.eq(Some(0)),
+            // the hacked verdict must not be reused by identical submissions
+            submissions_schema::content_hash.eq(None::<String>),
+        ))
+        .execute(conn)?;
+
+        Ok(())
+    })
+}
+
+// Hacks left waiting by a restart, started again on startup.
+pub fn waiting_ids(conn: &PgConnection) -> ServiceResult<Vec<Uuid>> {
+    use crate::schema::hacks as hacks_schema;
+
+    Ok(hacks_schema::table
+        .filter(hacks_schema::state.eq(WAITING.to_owned()))
+        .select(hacks_schema::id)
+        .load(conn)?)
+}
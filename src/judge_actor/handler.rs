@@ -1,15 +1,26 @@
+use super::backend::JudgeBackendError;
+use super::health;
+use super::queue;
 use super::statistics::*;
-use super::utils::*;
+use super::utils::read_test_case_weights;
 use super::JudgeActor;
+use crate::metrics;
+use crate::models::judge_servers::JudgeOutcome;
 use crate::models::*;
 use crate::services::rank::utils::update_acm_rank_cache;
 use crate::services::region::utils::get_self_type;
-use crate::statics::JUDGE_SERVER_INFOS;
-use crate::statics::WAITING_QUEUE;
+use crate::services::submission::events;
+use crate::statics::{ACM_RANK_CACHE, RESULT_STATISTICS_CACHE};
 use actix::prelude::*;
 use diesel::prelude::*;
-use server_core::database::db_connection;
+use server_core::database::{db_connection, PooledConnection};
+use server_core::errors::{ServiceError, ServiceResult};
 use server_core::utils::time::get_cur_naive_date_time;
+use std::collections::HashMap;
+use std::time::Instant;
+use uuid::Uuid;
+
+const DEFAULT_FULL_SCORE: i32 = 100;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct StartJudge();
@@ -18,12 +29,35 @@ impl Message for StartJudge {
     type Result = ();
 }
 
+// Run settings right away, skipping the queue, used to run custom input.
+#[derive(Debug, Clone)]
+pub struct RunCode(pub submissions::JudgeSettings);
+
+impl Message for RunCode {
+    type Result = ServiceResult<submissions::JudgeResult>;
+}
+
+impl Handler<RunCode> for JudgeActor {
+    type Result = ServiceResult<submissions::JudgeResult>;
+
+    fn handle(&mut self, msg: RunCode, _: &mut Self::Context) -> Self::Result {
+        let judge_target = match self.backend.choose_target() {
+            Some(judge_target) => judge_target,
+            None => {
+                let hint = "No judge server is available, please try later.".to_owned();
+                return Err(ServiceError::InternalServerErrorWithHint(hint));
+            }
+        };
+
+        self.judge_unqueued(&judge_target, &msg.0)
+            .map_err(|e| ServiceError::InternalServerErrorWithHint(e.to_string()))
+    }
+}
+
 impl Handler<StartJudge> for JudgeActor {
     type Result = ();
 
     fn handle(&mut self, _msg: StartJudge, _: &mut Self::Context) -> Self::Result {
-        use crate::schema::submissions as submissions_schema;
-
         let conn = match db_connection(&self.pool) {
             Ok(conn) => conn,
             Err(_) => {
@@ -31,187 +65,429 @@ impl Handler<StartJudge> for JudgeActor {
             }
         };
 
-        let mut queue_size = {
-            let lock = WAITING_QUEUE.read().unwrap();
-            lock.len()
-        };
-        log::info!("queue_size: {}", queue_size);
-        while queue_size != 0 {
-            let server = choose_judge_server();
-            if server.is_none() {
+        health::run_probes(self.backend.as_ref(), self.judge_timeout);
+
+        match queue::size(&conn) {
+            Ok(queue_size) => log::info!("queue_size: {}", queue_size),
+            Err(_) => {
+                log::error!("Error counting judge queue.");
                 return;
             }
-            let (server_url, server_token) = server.unwrap();
-
-            let task_uuid = {
-                let mut lock = WAITING_QUEUE.write().unwrap();
-                lock.pop_front().clone().unwrap()
+        };
+        loop {
+            let judge_target = match self.backend.choose_target() {
+                Some(judge_target) => judge_target,
+                None => return,
             };
 
-            let cur_state = match submissions_schema::table
-                .filter(submissions_schema::id.eq(task_uuid))
-                .select(submissions_schema::state)
-                .first::<String>(&conn)
-            {
-                Ok(cur_state) => cur_state,
+            let task = match queue::claim(&conn, self.starvation_timeout) {
+                Ok(Some(task)) => task,
+                Ok(None) => return,
                 Err(_) => {
-                    log::error!("Error loading setting_data from submissions.");
+                    log::error!("Error claiming task from judge queue.");
                     return;
                 }
             };
 
-            if cur_state == *"Waiting" {
-                // run judge
-                let setting_string = match submissions_schema::table
-                    .filter(submissions_schema::id.eq(task_uuid))
-                    .select(submissions_schema::settings)
-                    .first::<String>(&conn)
-                {
-                    Ok(setting_string) => setting_string,
-                    Err(_) => {
-                        log::error!("Error loading setting_data from submissions.");
-                        return;
-                    }
-                };
-
-                let target = submissions_schema::table.filter(submissions_schema::id.eq(task_uuid));
-                match diesel::update(target)
-                    .set((submissions_schema::state.eq("Pending".to_owned()),))
-                    .execute(&conn)
-                {
-                    Ok(_) => (),
-                    Err(_) => {
-                        log::error!("Error changing submissions's state to Pending.");
-                        return;
-                    }
-                };
-
-                info!("sending request to {}", server_url);
-                {
-                    let mut lock = JUDGE_SERVER_INFOS.write().unwrap();
-                    let mut server_info = lock.get_mut(&server_url).unwrap();
-                    server_info.task_number += 1;
-                }
-                let result_string =
-                    run_judge_client(server_token, server_url.clone(), setting_string);
-                info!("{}", result_string);
-
-                {
-                    let mut lock = JUDGE_SERVER_INFOS.write().unwrap();
-                    let mut server_info = lock.get_mut(&server_url).unwrap();
-                    server_info.task_number -= 1;
+            // a claimed task is never left leased, what could not be judged goes back to the queue
+            if let Err(e) = self.judge_task(&conn, &task, &judge_target) {
+                log::error!("Error judging {}: {}", task.submission_id, e);
+                if let Err(e) = self.give_back(&conn, &task, e.to_string()) {
+                    log::error!(
+                        "Error giving {} back to judge queue: {}",
+                        task.submission_id,
+                        e
+                    );
                 }
+            }
+        }
+    }
+}
 
-                if result_string == *"" {
-                    let target =
-                        submissions_schema::table.filter(submissions_schema::id.eq(task_uuid));
-                    match diesel::update(target)
-                        .set((submissions_schema::state.eq("Waiting".to_owned()),))
-                        .execute(&conn)
-                    {
-                        Ok(_) => (),
-                        Err(_) => {
-                            log::error!("Error changing submissions's state to Waiting.");
-                            return;
-                        }
-                    };
-
-                    {
-                        let mut lock = WAITING_QUEUE.write().unwrap();
-                        lock.push_front(task_uuid);
-                    }
+impl JudgeActor {
+    // Judge settings which are not in the queue, counted in metrics and server health
+    // the same way as queued ones.
+    pub(super) fn judge_unqueued(
+        &self,
+        judge_target: &str,
+        settings: &submissions::JudgeSettings,
+    ) -> Result<submissions::JudgeResult, JudgeBackendError> {
+        let start = Instant::now();
+        let judge_result = self
+            .backend
+            .judge(judge_target, settings, self.judge_timeout);
+        metrics::observe_judge(judge_target, start.elapsed());
 
-                    info!("pushed {} back to queue", task_uuid);
-                    continue;
-                }
+        let outcome = health::outcome_of(&judge_result);
+        if let Some(affected_ids) =
+            outcome.and_then(|outcome| health::record(judge_target, outcome, None))
+        {
+            let requeued =
+                db_connection(&self.pool).and_then(|conn| requeue_finished(&conn, &affected_ids));
+            if requeued.is_err() {
+                log::error!("Error requeueing submissions judged by {}.", judge_target);
+            }
+        }
+        judge_result
+    }
 
-                let raw_result =
-                    serde_json::from_str::<submissions::RawJudgeResult>(&result_string).unwrap();
-                let result = submissions::JudgeResult::from(raw_result);
-
-                // update submissions
-                let target = submissions_schema::table.filter(submissions_schema::id.eq(task_uuid));
-                let mut result_set = std::collections::HashSet::new();
-                match diesel::update(target)
-                    .set((
-                        submissions_schema::state.eq("Finished".to_owned()),
-                        submissions_schema::result.eq(serde_json::to_string(&result).unwrap()),
-                        submissions_schema::is_accepted.eq(result.is_accepted),
-                        submissions_schema::finish_time.eq(get_cur_naive_date_time()),
-                        submissions_schema::max_time.eq(result.max_time),
-                        submissions_schema::max_memory.eq(result.max_memory),
-                        submissions_schema::err.eq(result.err),
-                        submissions_schema::out_results.eq({
-                            if let Some(details) = result.details {
-                                let mut res = Vec::new();
-                                for detail in details {
-                                    result_set.insert(detail.result);
-                                }
-                                for e in result_set.clone() {
-                                    res.push(e);
-                                }
-                                Some(res)
-                            } else {
-                                None
-                            }
-                        }),
-                    ))
-                    .execute(&conn)
-                {
-                    Ok(_) => (),
-                    Err(_) => {
-                        log::error!("Error changing submissions's data.");
-                        return;
-                    }
-                };
-
-                let submission = submissions::Submission::from(
-                    match submissions_schema::table
-                        .filter(submissions_schema::id.eq(task_uuid))
-                        .first::<submissions::RawSubmission>(&conn)
-                    {
-                        Ok(raw_submission) => raw_submission,
-                        Err(_) => {
-                            log::error!("Error querying submission.");
-                            return;
-                        }
-                    },
-                );
-
-                match common_region::update_results(&conn, submission.clone()) {
-                    Ok(_) => {}
-                    Err(_) => {
-                        log::error!("Error updating results type.");
-                        return;
-                    }
-                };
-
-                if let Some(region) = submission.region.clone() {
-                    if match get_self_type(region, &conn) {
-                        Ok(region_type) => region_type,
-                        Err(_) => {
-                            log::error!("Error getting region type.");
-                            return;
-                        }
-                    } == "contest"
-                    {
-                        match update_acm_rank_cache(submission.region.unwrap(), &conn, false) {
-                            Ok(_) => (),
-                            Err(_) => {
-                                log::error!("Error updating acm rank cache.");
-                                return;
-                            }
-                        };
+    fn judge_task(
+        &self,
+        conn: &PooledConnection,
+        task: &judge_queue::JudgeQueueItem,
+        judge_target: &str,
+    ) -> ServiceResult<()> {
+        use crate::schema::submissions as submissions_schema;
+
+        let task_uuid = task.submission_id;
+        let cur_state: String = submissions_schema::table
+            .filter(submissions_schema::id.eq(task_uuid))
+            .select(submissions_schema::state)
+            .first(conn)?;
+
+        if cur_state != "Waiting" {
+            log::info!("{} is not waiting, dropping it from queue", task_uuid);
+            return queue::remove(conn, task_uuid);
+        }
+
+        // run judge
+        let setting_string: String = submissions_schema::table
+            .filter(submissions_schema::id.eq(task_uuid))
+            .select(submissions_schema::settings)
+            .first(conn)?;
+
+        let settings = match serde_json::from_str::<submissions::JudgeSettings>(&setting_string) {
+            Ok(settings) => settings,
+            Err(_) => {
+                log::error!("Error parsing settings of submission {}.", task_uuid);
+                let reason = "Broken judge settings.".to_owned();
+                mark_system_error(conn, task_uuid, reason)?;
+                notify(conn, task_uuid);
+                return Ok(());
+            }
+        };
+
+        let target = submissions_schema::table.filter(submissions_schema::id.eq(task_uuid));
+        diesel::update(target)
+            .set((submissions_schema::state.eq("Pending".to_owned()),))
+            .execute(conn)?;
+        notify(conn, task_uuid);
+        if events::publish_queue_positions(conn).is_err() {
+            log::error!("Error publishing queue positions.");
+        }
+
+        info!("judging {} on {}", task_uuid, judge_target);
+        let start = Instant::now();
+        let judge_result = self
+            .backend
+            .judge(judge_target, &settings, self.judge_timeout);
+        metrics::observe_judge(judge_target, start.elapsed());
+
+        // when this server has just been quarantined, what it judged wrongly goes back
+        // to the queue, including this submission
+        let outcome = health::outcome_of(&judge_result);
+        let judge_result = match outcome
+            .and_then(|outcome| health::record(judge_target, outcome, Some(task_uuid)))
+        {
+            Some(affected_ids) => {
+                let affected_ids: Vec<Uuid> = affected_ids
+                    .into_iter()
+                    .filter(|affected_id| *affected_id != task_uuid)
+                    .collect();
+                if requeue_finished(conn, &affected_ids).is_err() {
+                    log::error!("Error requeueing submissions judged by {}.", judge_target);
+                }
+                match judge_result {
+                    Ok(_) if outcome == Some(JudgeOutcome::SystemError) => {
+                        Err(JudgeBackendError::Quarantined(judge_target.to_owned()))
                     }
+                    judge_result => judge_result,
                 }
             }
+            None => judge_result,
+        };
 
-            queue_size = {
-                let lock = WAITING_QUEUE.read().unwrap();
-                lock.len()
-            };
+        let mut result = match judge_result {
+            Ok(result) => result,
+            // no other attempt can do better
+            Err(e @ JudgeBackendError::Unsupported(_)) => {
+                log::error!("Error judging {} on {}: {}", task_uuid, judge_target, e);
+                mark_system_error(conn, task_uuid, e.to_string())?;
+                notify(conn, task_uuid);
+                return Ok(());
+            }
+            Err(e) => {
+                log::error!("Error judging {} on {}: {}", task_uuid, judge_target, e);
+                return self.give_back(conn, task, e.to_string());
+            }
+        };
+
+        let score = score_result(conn, task_uuid, &settings, &mut result)?;
+
+        // update submissions
+        let target = submissions_schema::table.filter(submissions_schema::id.eq(task_uuid));
+        let out_results: Option<Vec<String>> = result
+            .out_results()
+            .map(|result_set| result_set.into_iter().collect());
+        conn.transaction::<_, ServiceError, _>(|| {
+            diesel::update(target)
+                .set((
+                    submissions_schema::state.eq("Finished".to_owned()),
+                    submissions_schema::result.eq(serde_json::to_string(&result).unwrap()),
+                    submissions_schema::is_accepted.eq(result.is_accepted),
+                    submissions_schema::finish_time.eq(get_cur_naive_date_time()),
+                    submissions_schema::max_time.eq(result.max_time),
+                    submissions_schema::max_memory.eq(result.max_memory),
+                    submissions_schema::err.eq(result.err.clone()),
+                    submissions_schema::out_results.eq(out_results),
+                    submissions_schema::score.eq(Some(score)),
+                ))
+                .execute(conn)?;
+
+            queue::remove(conn, task_uuid)
+        })?;
+
+        // the submission is finished whatever happens here
+        if after_finish(conn, task_uuid).is_err() {
+            log::error!("Error updating statistics and ranks of {}.", task_uuid);
+        }
+
+        Ok(())
+    }
+
+    // Put a task which could not be judged back to the queue with a backoff,
+    // or give up as SystemError once it failed `max_attempts` times.
+    fn give_back(
+        &self,
+        conn: &PgConnection,
+        task: &judge_queue::JudgeQueueItem,
+        reason: String,
+    ) -> ServiceResult<()> {
+        use crate::schema::submissions as submissions_schema;
+
+        let task_uuid = task.submission_id;
+        let attempts = task.attempts + 1;
+        if attempts >= self.max_attempts {
+            let reason = format!("Judge failed {} times: {}", attempts, reason);
+            mark_system_error(conn, task_uuid, reason)?;
+            notify(conn, task_uuid);
+            info!("gave up {} after {} attempts", task_uuid, attempts);
+            return Ok(());
+        }
+
+        // backoff doubles on every attempt, heartbeats will start judging again
+        let retry_time = get_cur_naive_date_time()
+            + self.retry_backoff * 2i32.pow((attempts - 1).min(10) as u32);
+        conn.transaction::<_, ServiceError, _>(|| {
+            let target = submissions_schema::table.filter(submissions_schema::id.eq(task_uuid));
+            diesel::update(target)
+                .set((submissions_schema::state.eq("Waiting".to_owned()),))
+                .execute(conn)?;
+
+            queue::retry(conn, task_uuid, retry_time)
+        })?;
+
+        notify(conn, task_uuid);
+        info!(
+            "pushed {} back to queue, retry at {}",
+            task_uuid, retry_time
+        );
+        Ok(())
+    }
+}
+
+// Score of a result by the weights and subtasks of its problem, filling in subtask results.
+pub fn score_result(
+    conn: &PgConnection,
+    submission_id: Uuid,
+    settings: &submissions::JudgeSettings,
+    result: &mut submissions::JudgeResult,
+) -> ServiceResult<i32> {
+    let full_score = full_score_of(conn, submission_id)?;
+    // inline test cases of samples and tests have neither weights nor subtasks
+    let (weights, subtasks) = match settings.test_case_id.as_ref() {
+        Some(test_case_id) => (
+            read_test_case_weights(test_case_id),
+            subtasks_of(conn, submission_id)?,
+        ),
+        None => (HashMap::new(), Vec::new()),
+    };
+
+    Ok(if subtasks.is_empty() {
+        result.score(&weights, full_score)
+    } else {
+        let subtask_results = result.fold_subtasks(&subtasks, &weights);
+        let score = submissions::subtasks_score(&subtask_results, full_score);
+        result.subtasks = Some(subtask_results);
+        score
+    })
+}
+
+// Tell subscribers about a finished submission and count it in statistics and ranks.
+pub fn after_finish(conn: &PooledConnection, submission_id: Uuid) -> ServiceResult<()> {
+    use crate::schema::submissions as submissions_schema;
+
+    notify(conn, submission_id);
+
+    let submission = submissions::Submission::from(
+        submissions_schema::table
+            .filter(submissions_schema::id.eq(submission_id))
+            .first::<submissions::RawSubmission>(conn)?,
+    );
+    metrics::count_verdict(&submission);
+    common_region::update_results(conn, submission.clone())?;
+
+    if let Some(region) = submission.region {
+        if get_self_type(region.clone(), conn)? == "contest" {
+            update_acm_rank_cache(region, conn, false)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Put finished submissions back into the queue, like a rejudge without a record.
+fn requeue_finished(conn: &PgConnection, submission_ids: &[Uuid]) -> ServiceResult<()> {
+    use crate::schema::submissions as submissions_schema;
+
+    if submission_ids.is_empty() {
+        return Ok(());
+    }
+
+    let requeued: Vec<(Uuid, Option<String>, i32)> =
+        conn.transaction::<_, ServiceError, _>(|| {
+            let requeued: Vec<(Uuid, Option<String>, i32)> = submissions_schema::table
+                .filter(submissions_schema::id.eq_any(submission_ids))
+                .filter(submissions_schema::state.eq("Finished".to_owned()))
+                .select((
+                    submissions_schema::id,
+                    submissions_schema::region,
+                    submissions_schema::problem_id,
+                ))
+                .load(conn)?;
+
+            for (submission_id, region, _) in requeued.iter() {
+                diesel::update(
+                    submissions_schema::table.filter(submissions_schema::id.eq(submission_id)),
+                )
+                .set((
+                    submissions_schema::state.eq("Waiting".to_owned()),
+                    submissions_schema::result.eq(None::<String>),
+                    submissions_schema::is_accepted.eq(None::<bool>),
+                    submissions_schema::finish_time.eq(None::<chrono::NaiveDateTime>),
+                    submissions_schema::max_time.eq(None::<i32>),
+                    submissions_schema::max_memory.eq(None::<i32>),
+                    submissions_schema::err.eq(None::<String>),
+                    submissions_schema::out_results.eq(None::<Vec<String>>),
+                    submissions_schema::score.eq(None::<i32>),
+                ))
+                .execute(conn)?;
+
+                let lane = queue::lane_of(conn, region.as_deref())?;
+                queue::push(conn, *submission_id, lane)?;
+            }
+
+            Ok(requeued)
+        })?;
+
+    // statistics and ranks are rebuilt from the database on next access
+    {
+        let mut result_statistics = RESULT_STATISTICS_CACHE.write().unwrap();
+        let mut acm_ranks = ACM_RANK_CACHE.write().unwrap();
+        for (_, region, problem_id) in requeued.iter() {
+            if let Some(region) = region.clone() {
+                result_statistics.remove(&(region.clone(), *problem_id));
+                acm_ranks.remove(&region);
+            }
         }
+    }
+
+    for (submission_id, _, _) in requeued.iter() {
+        notify(conn, *submission_id);
+    }
+    info!("requeued {} submissions", requeued.len());
+
+    Ok(())
+}
+
+// Retries are exhausted or the task can never be judged, keep the reason in `err`.
+fn mark_system_error(
+    conn: &PgConnection,
+    submission_id: Uuid,
+    reason: String,
+) -> ServiceResult<()> {
+    use crate::schema::submissions as submissions_schema;
+
+    conn.transaction(|| {
+        let target = submissions_schema::table.filter(submissions_schema::id.eq(submission_id));
+        diesel::update(target)
+            .set((
+                submissions_schema::state.eq("SystemError".to_owned()),
+                submissions_schema::finish_time.eq(get_cur_naive_date_time()),
+                submissions_schema::err.eq(reason),
+            ))
+            .execute(conn)?;
+
+        queue::remove(conn, submission_id)
+    })
+}
+
+// The score of the linked problem in the region, 100 when judged outside of a region.
+fn full_score_of(conn: &PgConnection, submission_id: Uuid) -> ServiceResult<i32> {
+    use crate::schema::region_links as region_links_schema;
+    use crate::schema::submissions as submissions_schema;
+
+    let (region, problem_id): (Option<String>, i32) = submissions_schema::table
+        .filter(submissions_schema::id.eq(submission_id))
+        .select((submissions_schema::region, submissions_schema::problem_id))
+        .first(conn)?;
+
+    let region = match region {
+        Some(region) => region,
+        None => return Ok(DEFAULT_FULL_SCORE),
+    };
+
+    let score: Option<Option<i32>> = region_links_schema::table
+        .filter(region_links_schema::region.eq(region))
+        .filter(region_links_schema::problem_id.eq(problem_id))
+        .select(region_links_schema::score)
+        .first(conn)
+        .optional()?;
+
+    Ok(score.flatten().unwrap_or(DEFAULT_FULL_SCORE))
+}
+
+fn subtasks_of(conn: &PgConnection, submission_id: Uuid) -> ServiceResult<Vec<problems::Subtask>> {
+    use crate::schema::problems as problems_schema;
+    use crate::schema::submissions as submissions_schema;
+
+    let problem_id: i32 = submissions_schema::table
+        .filter(submissions_schema::id.eq(submission_id))
+        .select(submissions_schema::problem_id)
+        .first(conn)?;
+
+    // the problem may be gone while its submissions are still judged
+    let raw_settings: String = match problems_schema::table
+        .filter(problems_schema::id.eq(problem_id))
+        .select(problems_schema::settings)
+        .first(conn)
+        .optional()?
+    {
+        Some(raw_settings) => raw_settings,
+        None => return Ok(Vec::new()),
+    };
+    let settings: problems::ProblemSettings =
+        serde_json::from_str(&raw_settings).map_err(|_| {
+            let hint = format!("Broken settings in problem {}.", problem_id);
+            ServiceError::InternalServerErrorWithHint(hint)
+        })?;
+
+    Ok(settings.subtasks.unwrap_or_default())
+}
 
-        ()
+// Push the new state to live subscribers, failing here must not stop judging.
+pub(super) fn notify(conn: &PgConnection, submission_id: Uuid) {
+    if events::publish_submission(conn, submission_id).is_err() {
+        log::error!("Error publishing submission {}.", submission_id);
     }
 }
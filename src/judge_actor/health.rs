@@ -0,0 +1,191 @@
+use super::backend::{JudgeBackend, JudgeBackendError};
+use super::client::JudgeClientError;
+use crate::models::judge_servers::{JudgeOutcome, JudgeServerHealth};
+use crate::models::languages::get_lang_config;
+use crate::models::submissions::{JudgeResult, JudgeSettings, TestCase, COMPILE_ERROR_ERR};
+use crate::statics::JUDGE_SERVER_INFOS;
+use server_core::errors::ServiceResult;
+use std::time::{Duration, SystemTime};
+use uuid::Uuid;
+
+// outcomes kept per server
+const HEALTH_WINDOW: usize = 20;
+// a server is quarantined when it crosses any of these
+const MAX_CONSECUTIVE_FAILURES: i32 = 3;
+const MAX_SYSTEM_ERROR_RATIO: f32 = 0.5;
+const MIN_OUTCOMES_FOR_RATIO: usize = 6;
+const MAX_TIMEOUTS: usize = 3;
+
+const QUARANTINE_COOLDOWN: Duration = Duration::from_secs(60);
+const MAX_QUARANTINE_COOLDOWN: Duration = Duration::from_secs(30 * 60);
+// probe submissions a server has to pass in a row to be trusted again
+const PROBE_COUNT: usize = 2;
+
+const PROBE_SRC: &str = "#include <stdio.h>\nint main() { int a, b; scanf(\"%d %d\", &a, &b); printf(\"%d\\n\", a + b); return 0; }";
+
+// A SYSTEM_ERROR the server is to blame for, errors of special judges come from the problem.
+pub fn is_system_error(result: &JudgeResult) -> bool {
+    let is_server_error = match result.err.as_deref() {
+        None | Some(COMPILE_ERROR_ERR) => false,
+        Some(_) => true,
+    };
+    is_server_error
+        || result
+            .details
+            .iter()
+            .flatten()
+            .any(|detail| detail.result == "SYSTEM_ERROR" && detail.error != "SPJ_ERROR")
+}
+
+// None when the server is not to blame for the result, so it is not counted.
+pub fn outcome_of(judge_result: &Result<JudgeResult, JudgeBackendError>) -> Option<JudgeOutcome> {
+    Some(match judge_result {
+        Ok(result) if is_system_error(result) => JudgeOutcome::SystemError,
+        Ok(_) => JudgeOutcome::Success,
+        Err(JudgeBackendError::Client(JudgeClientError::Timeout)) => JudgeOutcome::Timeout,
+        Err(JudgeBackendError::Unsupported(_)) => return None,
+        Err(_) => JudgeOutcome::Failed,
+    })
+}
+
+fn crosses_thresholds(health: &JudgeServerHealth) -> bool {
+    health.consecutive_failures >= MAX_CONSECUTIVE_FAILURES
+        || (health.recent_outcomes.len() >= MIN_OUTCOMES_FOR_RATIO
+            && health.system_error_ratio() >= MAX_SYSTEM_ERROR_RATIO)
+        || health.count(JudgeOutcome::Timeout) >= MAX_TIMEOUTS
+}
+
+fn cooldown(quarantine_count: u32) -> Duration {
+    (QUARANTINE_COOLDOWN * 2u32.pow(quarantine_count.min(10))).min(MAX_QUARANTINE_COOLDOWN)
+}
+
+fn quarantine(service_url: &str, health: &mut JudgeServerHealth) {
+    let cooldown = cooldown(health.quarantine_count);
+    log::warn!(
+        "quarantining {} for {}s (consecutive failures: {}, system error ratio: {:.2}, timeouts: {})",
+        service_url,
+        cooldown.as_secs(),
+        health.consecutive_failures,
+        health.system_error_ratio(),
+        health.count(JudgeOutcome::Timeout)
+    );
+    health.quarantined_until = Some(SystemTime::now() + cooldown);
+    health.quarantine_count += 1;
+    health.is_probing = false;
+}
+
+// Count the outcome of a judge request, returns the submissions finished with a SYSTEM_ERROR
+// of this server when it has just been quarantined, so they can be judged again elsewhere.
+pub fn record(
+    service_url: &str,
+    outcome: JudgeOutcome,
+    submission_id: Option<Uuid>,
+) -> Option<Vec<Uuid>> {
+    let mut lock = JUDGE_SERVER_INFOS.write().unwrap();
+    let health = &mut lock.get_mut(service_url)?.health;
+    // tasks sent before the quarantine may still come back
+    if health.is_quarantined() {
+        return None;
+    }
+
+    health.recent_outcomes.push_front((outcome, submission_id));
+    health.recent_outcomes.truncate(HEALTH_WINDOW);
+    health.consecutive_failures = match outcome {
+        JudgeOutcome::Success => 0,
+        _ => health.consecutive_failures + 1,
+    };
+    if !crosses_thresholds(health) {
+        return None;
+    }
+
+    quarantine(service_url, health);
+    Some(
+        health
+            .recent_outcomes
+            .iter()
+            .filter(|(outcome, _)| *outcome == JudgeOutcome::SystemError)
+            .filter_map(|(_, submission_id)| *submission_id)
+            .collect(),
+    )
+}
+
+// Quarantined servers whose cool-down is over, marked so only one actor probes each of them.
+fn claim_probes() -> Vec<String> {
+    let now = SystemTime::now();
+    let mut lock = JUDGE_SERVER_INFOS.write().unwrap();
+    let mut res = Vec::new();
+    for info in lock.values_mut() {
+        match info.health.quarantined_until {
+            Some(until) if until <= now && !info.health.is_probing && !info.is_deprecated => {
+                info.health.is_probing = true;
+                res.push(info.service_url.clone());
+            }
+            _ => (),
+        }
+    }
+    res
+}
+
+fn probe_settings() -> ServiceResult<JudgeSettings> {
+    Ok(JudgeSettings {
+        language_config: get_lang_config("c")?,
+        src: PROBE_SRC.to_owned(),
+        max_cpu_time: 1000,
+        max_memory: 256 * 1024 * 1024,
+        test_case_id: None,
+        test_case: Some(vec![
+            TestCase {
+                input: "1 2\n".to_owned(),
+                output: "3\n".to_owned(),
+            },
+            TestCase {
+                input: "-5 5\n".to_owned(),
+                output: "0\n".to_owned(),
+            },
+        ]),
+        spj_version: None,
+        spj_config: None,
+        spj_compile_config: None,
+        spj_src: None,
+        checker: None,
+        interactor_config: None,
+        interactor_compile_config: None,
+        interactor_src: None,
+        comparator: None,
+        output: false,
+    })
+}
+
+fn finish_probes(service_url: &str, passed: bool) {
+    let mut lock = JUDGE_SERVER_INFOS.write().unwrap();
+    let health = match lock.get_mut(service_url) {
+        Some(info) => &mut info.health,
+        None => return,
+    };
+    if passed {
+        info!("{} passed the probes and is trusted again", service_url);
+        *health = JudgeServerHealth::default();
+    } else {
+        quarantine(service_url, health);
+    }
+}
+
+// Send probe submissions to every server done with its cool-down.
+pub fn run_probes(backend: &dyn JudgeBackend, timeout: Duration) {
+    for service_url in claim_probes() {
+        let settings = match probe_settings() {
+            Ok(settings) => settings,
+            Err(_) => {
+                log::error!("Probes are written in C, which is missing from languages.toml.");
+                finish_probes(&service_url, false);
+                continue;
+            }
+        };
+        let passed =
+            (0..PROBE_COUNT).all(|_| match backend.judge(&service_url, &settings, timeout) {
+                Ok(result) => result.err.is_none() && result.is_accepted == Some(true),
+                Err(_) => false,
+            });
+        finish_probes(&service_url, passed);
+    }
+}
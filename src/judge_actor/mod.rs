@@ -1,12 +1,33 @@
+pub mod backend;
+mod checker;
+mod client;
+mod compare;
+pub mod hack;
 pub mod handler;
+mod health;
+pub mod queue;
+mod scheduling;
 mod statistics;
+#[cfg(test)]
+mod tests;
 mod utils;
 
 use actix::prelude::*;
-use server_core::database::Pool;
+use backend::JudgeBackend;
+use handler::StartJudge;
+use server_core::database::{db_connection, Pool};
+use std::sync::Arc;
+use std::time::Duration;
+
+const TICK_INTERVAL: Duration = Duration::from_secs(5);
 
 pub struct JudgeActor {
     pub pool: Pool,
+    pub judge_timeout: Duration,
+    pub starvation_timeout: chrono::Duration,
+    pub max_attempts: i32,
+    pub retry_backoff: chrono::Duration,
+    pub backend: Arc<dyn JudgeBackend>,
 }
 
 impl Actor for JudgeActor {
@@ -23,7 +44,57 @@ pub(crate) fn start_judge_actor(opt: server_core::cli_args::Opt, pool: Pool) ->
         opt.judge_actor_count
     );
 
-    SyncArbiter::start(opt.judge_actor_count, move || JudgeActor {
+    let lease_timeout = chrono::Duration::seconds(opt.judge_lease_timeout_in_sec as i64);
+    match db_connection(&pool).map(|conn| queue::reclaim(&conn, lease_timeout)) {
+        Ok(Ok(count)) => info!(
+            "Reclaimed {} unfinished submissions into judge queue",
+            count
+        ),
+        _ => log::error!("Error reclaiming unfinished submissions."),
+    }
+
+    let waiting_hack_ids = match db_connection(&pool).map(|conn| hack::waiting_ids(&conn)) {
+        Ok(Ok(hack_ids)) => hack_ids,
+        _ => {
+            log::error!("Error loading waiting hacks.");
+            Vec::new()
+        }
+    };
+
+    let judge_timeout = Duration::from_secs(opt.judge_timeout_in_sec);
+    let starvation_timeout = chrono::Duration::seconds(opt.queue_starvation_timeout_in_sec as i64);
+    let max_attempts = opt.judge_max_attempts.max(1);
+    let retry_backoff = chrono::Duration::seconds(opt.judge_retry_backoff_in_sec as i64);
+    let backend = backend::from_opt(&opt);
+    let ticker_pool = pool.clone();
+    info!("Judging with {} backend", backend.name());
+    let addr = SyncArbiter::start(opt.judge_actor_count, move || JudgeActor {
         pool: pool.clone(),
-    })
+        judge_timeout,
+        starvation_timeout,
+        max_attempts,
+        retry_backoff,
+        backend: backend.clone(),
+    });
+
+    for hack_id in waiting_hack_ids {
+        addr.do_send(hack::StartHack(hack_id));
+    }
+
+    // heartbeats of judge servers wake the actors up as well,
+    // but tasks backing off must not wait for them
+    let ticker_addr = addr.clone();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(TICK_INTERVAL);
+        match db_connection(&ticker_pool).map(|conn| queue::release_expired(&conn, lease_timeout)) {
+            Ok(Ok(expired_ids)) if !expired_ids.is_empty() => {
+                info!("Released {} expired judge tasks", expired_ids.len())
+            }
+            Ok(Ok(_)) => (),
+            _ => log::error!("Error releasing expired judge tasks."),
+        }
+        ticker_addr.do_send(StartJudge());
+    });
+
+    addr
 }
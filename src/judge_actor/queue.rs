@@ -0,0 +1,250 @@
+use crate::models::contests::{get_contest_state, Contest, ContestState, RawContest};
+use crate::models::judge_queue::*;
+use crate::schema::contests as contests_schema;
+use crate::schema::judge_queue as judge_queue_schema;
+use crate::schema::submissions as submissions_schema;
+use chrono::Duration;
+use diesel::prelude::*;
+use server_core::errors::ServiceResult;
+use server_core::utils::time::get_cur_naive_date_time;
+use uuid::Uuid;
+
+// Submissions of a running contest go first, samples and tests without region go last.
+pub fn lane_of(conn: &PgConnection, region: Option<&str>) -> ServiceResult<i32> {
+    let region = match region {
+        Some(region) => region,
+        None => return Ok(SAMPLE_LANE),
+    };
+
+    let raw_contest: Option<RawContest> = contests_schema::table
+        .filter(contests_schema::region.eq(region))
+        .first(conn)
+        .optional()?;
+
+    Ok(match raw_contest {
+        Some(raw_contest) => {
+            match get_contest_state(Contest::from(raw_contest), get_cur_naive_date_time()) {
+                ContestState::Running | ContestState::SealedRunning => CONTEST_LANE,
+                _ => PRACTICE_LANE,
+            }
+        }
+        None => PRACTICE_LANE,
+    })
+}
+
+pub fn push(conn: &PgConnection, submission_id: Uuid, lane: i32) -> ServiceResult<()> {
+    diesel::insert_into(judge_queue_schema::table)
+        .values(&JudgeQueueItem {
+            submission_id,
+            enqueue_time: get_cur_naive_date_time(),
+            lease_time: None,
+            lane,
+            attempts: 0,
+            retry_time: None,
+        })
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+
+    Ok(())
+}
+
+// Take the oldest unclaimed task of the highest lane and lease it,
+// tasks backing off after a failure are skipped until their retry_time.
+// A task waiting longer than `starvation_timeout` is taken first whatever its lane,
+// so a busy contest can not hold back practice and samples forever.
+// Rows locked by other claimers are skipped instead of waited for.
+pub fn claim(
+    conn: &PgConnection,
+    starvation_timeout: Duration,
+) -> ServiceResult<Option<JudgeQueueItem>> {
+    Ok(conn.transaction::<_, diesel::result::Error, _>(|| {
+        let cur_time = get_cur_naive_date_time();
+        let claimable = judge_queue_schema::lease_time.is_null().and(
+            judge_queue_schema::retry_time
+                .is_null()
+                .or(judge_queue_schema::retry_time.le(cur_time)),
+        );
+
+        let starving: Option<JudgeQueueItem> = judge_queue_schema::table
+            .filter(claimable)
+            .filter(judge_queue_schema::enqueue_time.lt(cur_time - starvation_timeout))
+            .order(judge_queue_schema::enqueue_time.asc())
+            .for_update()
+            .skip_locked()
+            .first(conn)
+            .optional()?;
+
+        let item: Option<JudgeQueueItem> = match starving {
+            Some(item) => Some(item),
+            None => judge_queue_schema::table
+                .filter(claimable)
+                .order((
+                    judge_queue_schema::lane.asc(),
+                    judge_queue_schema::enqueue_time.asc(),
+                ))
+                .for_update()
+                .skip_locked()
+                .first(conn)
+                .optional()?,
+        };
+
+        if let Some(item) = item.clone() {
+            diesel::update(
+                judge_queue_schema::table
+                    .filter(judge_queue_schema::submission_id.eq(item.submission_id)),
+            )
+            .set(judge_queue_schema::lease_time.eq(Some(cur_time)))
+            .execute(conn)?;
+        }
+
+        Ok(item)
+    })?)
+}
+
+// Give a failed task back to be claimed again after `retry_time`,
+// it keeps its enqueue_time so it stays at the head of its lane.
+pub fn retry(
+    conn: &PgConnection,
+    submission_id: Uuid,
+    retry_time: chrono::NaiveDateTime,
+) -> ServiceResult<()> {
+    diesel::update(
+        judge_queue_schema::table.filter(judge_queue_schema::submission_id.eq(submission_id)),
+    )
+    .set((
+        judge_queue_schema::lease_time.eq(None::<chrono::NaiveDateTime>),
+        judge_queue_schema::attempts.eq(judge_queue_schema::attempts + 1),
+        judge_queue_schema::retry_time.eq(Some(retry_time)),
+    ))
+    .execute(conn)?;
+
+    Ok(())
+}
+
+pub fn remove(conn: &PgConnection, submission_id: Uuid) -> ServiceResult<()> {
+    diesel::delete(
+        judge_queue_schema::table.filter(judge_queue_schema::submission_id.eq(submission_id)),
+    )
+    .execute(conn)?;
+
+    Ok(())
+}
+
+pub fn size(conn: &PgConnection) -> ServiceResult<i64> {
+    Ok(judge_queue_schema::table
+        .filter(judge_queue_schema::lease_time.is_null())
+        .count()
+        .get_result(conn)?)
+}
+
+// Unclaimed tasks in the order they are going to be judged, ignoring starvation and backoff.
+pub fn waiting_ids(conn: &PgConnection) -> ServiceResult<Vec<Uuid>> {
+    Ok(judge_queue_schema::table
+        .filter(judge_queue_schema::lease_time.is_null())
+        .order((
+            judge_queue_schema::lane.asc(),
+            judge_queue_schema::enqueue_time.asc(),
+        ))
+        .select(judge_queue_schema::submission_id)
+        .load(conn)?)
+}
+
+pub fn depth(conn: &PgConnection) -> ServiceResult<Vec<QueueDepth>> {
+    let mut res = Vec::new();
+    for (lane, name) in LANES.iter() {
+        let waiting: i64 = judge_queue_schema::table
+            .filter(judge_queue_schema::lane.eq(lane))
+            .filter(judge_queue_schema::lease_time.is_null())
+            .count()
+            .get_result(conn)?;
+        let judging: i64 = judge_queue_schema::table
+            .filter(judge_queue_schema::lane.eq(lane))
+            .filter(judge_queue_schema::lease_time.is_not_null())
+            .count()
+            .get_result(conn)?;
+
+        res.push(QueueDepth {
+            lane: name.to_string(),
+            waiting,
+            judging,
+        });
+    }
+
+    Ok(res)
+}
+
+// Puts back tasks whose lease has expired, their submissions are waiting again.
+// Also called periodically, so a task its actor failed to give back is not lost until restart.
+pub fn release_expired(conn: &PgConnection, lease_timeout: Duration) -> ServiceResult<Vec<Uuid>> {
+    conn.transaction(|| {
+        let expired_ids: Vec<Uuid> = judge_queue_schema::table
+            .filter(judge_queue_schema::lease_time.lt(get_cur_naive_date_time() - lease_timeout))
+            .select(judge_queue_schema::submission_id)
+            .load(conn)?;
+
+        diesel::update(
+            judge_queue_schema::table
+                .filter(judge_queue_schema::submission_id.eq_any(expired_ids.clone())),
+        )
+        .set(judge_queue_schema::lease_time.eq(None::<chrono::NaiveDateTime>))
+        .execute(conn)?;
+
+        diesel::update(
+            submissions_schema::table
+                .filter(submissions_schema::id.eq_any(expired_ids.clone()))
+                .filter(submissions_schema::state.eq("Pending".to_owned())),
+        )
+        .set(submissions_schema::state.eq("Waiting".to_owned()))
+        .execute(conn)?;
+
+        Ok(expired_ids)
+    })
+}
+
+// Called on startup, puts back tasks whose lease has expired
+// and enqueues unfinished submissions which are missing from the queue.
+pub fn reclaim(conn: &PgConnection, lease_timeout: Duration) -> ServiceResult<usize> {
+    conn.transaction(|| {
+        let expired_ids = release_expired(conn, lease_timeout)?;
+
+        let queued_ids = judge_queue_schema::table.select(judge_queue_schema::submission_id);
+        let orphans: Vec<(Uuid, Option<String>, chrono::NaiveDateTime)> = submissions_schema::table
+            .filter(
+                submissions_schema::state
+                    .eq("Pending".to_owned())
+                    .or(submissions_schema::state.eq("Waiting".to_owned())),
+            )
+            .filter(diesel::dsl::not(submissions_schema::id.eq_any(queued_ids)))
+            .select((
+                submissions_schema::id,
+                submissions_schema::region,
+                submissions_schema::submit_time,
+            ))
+            .load(conn)?;
+
+        let mut reclaimed_ids = expired_ids;
+        for (submission_id, region, submit_time) in orphans {
+            diesel::insert_into(judge_queue_schema::table)
+                .values(&JudgeQueueItem {
+                    submission_id,
+                    enqueue_time: submit_time,
+                    lease_time: None,
+                    lane: lane_of(conn, region.as_deref())?,
+                    attempts: 0,
+                    retry_time: None,
+                })
+                .execute(conn)?;
+            reclaimed_ids.push(submission_id);
+        }
+
+        diesel::update(
+            submissions_schema::table
+                .filter(submissions_schema::id.eq_any(reclaimed_ids.clone()))
+                .filter(submissions_schema::state.eq("Pending".to_owned())),
+        )
+        .set(submissions_schema::state.eq("Waiting".to_owned()))
+        .execute(conn)?;
+
+        Ok(reclaimed_ids.len())
+    })
+}
@@ -0,0 +1,144 @@
+use crate::models::judge_servers::JudgeServerInfo;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+pub trait SchedulingStrategy: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // `candidates` only contains servers able to take one more task, sorted by service_url
+    fn choose<'a>(&self, candidates: &[&'a JudgeServerInfo]) -> Option<&'a JudgeServerInfo>;
+}
+
+fn task_ratio(info: &JudgeServerInfo) -> f32 {
+    info.task_number as f32 / (info.cpu_core * 2).max(1) as f32
+}
+
+// Pick the server with the lowest task_number / capacity, prefer bigger machines on tie.
+pub struct LeastLoaded;
+
+impl SchedulingStrategy for LeastLoaded {
+    fn name(&self) -> &'static str {
+        "least_loaded"
+    }
+
+    fn choose<'a>(&self, candidates: &[&'a JudgeServerInfo]) -> Option<&'a JudgeServerInfo> {
+        let mut res: Option<&'a JudgeServerInfo> = None;
+        for &info in candidates {
+            res = match res {
+                Some(chosen)
+                    if task_ratio(chosen) < task_ratio(info)
+                        || (task_ratio(chosen) == task_ratio(info)
+                            && chosen.cpu_core >= info.cpu_core) =>
+                {
+                    Some(chosen)
+                }
+                _ => Some(info),
+            };
+        }
+        res
+    }
+}
+
+// Pick the server with the most idle cpu per running task, using the cpu usage in heartbeats.
+pub struct CpuWeighted;
+
+fn idle_weight(info: &JudgeServerInfo) -> f32 {
+    let idle = (100.0 - info.cpu.clamp(0.0, 100.0)) / 100.0;
+    info.cpu_core as f32 * idle / (info.task_number + 1) as f32
+}
+
+impl SchedulingStrategy for CpuWeighted {
+    fn name(&self) -> &'static str {
+        "cpu_weighted"
+    }
+
+    fn choose<'a>(&self, candidates: &[&'a JudgeServerInfo]) -> Option<&'a JudgeServerInfo> {
+        let mut res: Option<&'a JudgeServerInfo> = None;
+        for &info in candidates {
+            res = match res {
+                Some(chosen) if idle_weight(chosen) >= idle_weight(info) => Some(chosen),
+                _ => Some(info),
+            };
+        }
+        res
+    }
+}
+
+// Cycle through the servers, shared by every JudgeActor.
+#[derive(Default)]
+pub struct RoundRobin {
+    next: AtomicUsize,
+}
+
+impl SchedulingStrategy for RoundRobin {
+    fn name(&self) -> &'static str {
+        "round_robin"
+    }
+
+    fn choose<'a>(&self, candidates: &[&'a JudgeServerInfo]) -> Option<&'a JudgeServerInfo> {
+        if candidates.is_empty() {
+            return None;
+        }
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % candidates.len();
+        Some(candidates[index])
+    }
+}
+
+pub fn from_name(name: &str) -> Arc<dyn SchedulingStrategy> {
+    match name {
+        "cpu_weighted" => Arc::new(CpuWeighted),
+        "round_robin" => Arc::new(RoundRobin::default()),
+        _ => Arc::new(LeastLoaded),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+
+    fn server(url: &str, cpu_core: i32, cpu: f32, task_number: i32) -> JudgeServerInfo {
+        JudgeServerInfo {
+            judger_version: "2.0.0".to_owned(),
+            hostname: url.to_owned(),
+            cpu_core,
+            memory: 0.0,
+            cpu,
+            task_number,
+            service_url: url.to_owned(),
+            token: String::new(),
+            heartbeat_time: SystemTime::now(),
+            is_deprecated: false,
+            state: "Active".to_owned(),
+            health: Default::default(),
+            compiler_versions: Default::default(),
+        }
+    }
+
+    #[test]
+    fn least_loaded_prefers_lower_task_ratio() {
+        let small = server("a", 1, 0.0, 1);
+        let big = server("b", 8, 0.0, 4);
+        let chosen = LeastLoaded.choose(&[&small, &big]).unwrap();
+        assert_eq!(chosen.service_url, "b");
+    }
+
+    #[test]
+    fn cpu_weighted_avoids_busy_cpu() {
+        let busy = server("a", 4, 95.0, 0);
+        let idle = server("b", 2, 10.0, 0);
+        let chosen = CpuWeighted.choose(&[&busy, &idle]).unwrap();
+        assert_eq!(chosen.service_url, "b");
+    }
+
+    #[test]
+    fn round_robin_cycles() {
+        let a = server("a", 1, 0.0, 0);
+        let b = server("b", 1, 0.0, 0);
+        let strategy = RoundRobin::default();
+        let picks: Vec<String> = (0..3)
+            .map(|_| strategy.choose(&[&a, &b]).unwrap().service_url.clone())
+            .collect();
+        assert_eq!(picks, vec!["a", "b", "a"]);
+    }
+}
@@ -51,6 +51,7 @@ fn count_results(conn: &PooledConnection, region: &str, problem_id: i32) -> Serv
             memory_limit_exceeded: 0,
             runtime_error: 0,
             system_error: 0,
+            compile_error: 0,
             unknown_error: 0,
         },
     };
@@ -125,6 +126,9 @@ fn update_submission_statistics(statistics: &mut SubmissionStatistics, submissio
                 "SYSTEM_ERROR" => {
                     statistics.result_count.system_error += 1;
                 }
+                "COMPILE_ERROR" => {
+                    statistics.result_count.compile_error += 1;
+                }
                 "UNKNOWN_ERROR" => {
                     statistics.result_count.unknown_error += 1;
                 }
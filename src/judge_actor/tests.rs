@@ -0,0 +1,1259 @@
+// Ignored tests need what their reason says, run them with `cargo test -- --ignored`.
+// Those needing a database expect DATABASE_URL pointing to a migrated one.
+
+use super::backend::{HttpBackend, JudgeBackend, JudgeBackendError, LocalBackend};
+use super::checker::run_checker;
+use super::client::{run_judge_client, JudgeClientError};
+use super::hack::StartHack;
+use super::handler::StartJudge;
+use super::{health, queue, scheduling, JudgeActor};
+use crate::models::hacks::{self, Hack};
+use crate::models::judge_queue::SAMPLE_LANE;
+use crate::models::judge_servers::{self, JudgeOutcome, JudgeServerInfo};
+use crate::models::languages::{
+    checker_config, get_lang_config, interactor_compile_config, interactor_config, CheckerConfig,
+};
+use crate::models::problems::{test_case_dir, InsertableProblem, TEST_CASE_ROOT};
+use crate::models::region_links::RegionLink;
+use crate::models::regions::Region;
+use crate::models::submissions::{
+    InsertableSubmission, JudgeResult, JudgeSettings, RawSubmission, RunResult, TestCase,
+    COMPILE_ERROR, COMPILE_ERROR_ERR,
+};
+use crate::services::problem::utils::run_with_timeout;
+use crate::services::{language, region, rejudge, submission};
+use crate::statics::JUDGE_SERVER_INFOS;
+use actix::prelude::*;
+use diesel::prelude::*;
+use mock_judge_server::{MockJudgeServer, Reply};
+use server_core::database::Pool;
+use server_core::errors::ServiceError;
+use server_core::utils::time::get_cur_naive_date_time;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+use uuid::Uuid;
+
+fn settings(src: &str) -> JudgeSettings {
+    JudgeSettings {
+        language_config: get_lang_config("c").unwrap(),
+        src: src.to_owned(),
+        max_cpu_time: 1000,
+        max_memory: 256 * 1024 * 1024,
+        test_case_id: Some("mock".to_owned()),
+        test_case: None,
+        spj_version: None,
+        spj_config: None,
+        spj_compile_config: None,
+        spj_src: None,
+        checker: None,
+        interactor_config: None,
+        interactor_compile_config: None,
+        interactor_src: None,
+        comparator: None,
+        output: false,
+    }
+}
+
+fn judge(reply: Reply, timeout: Duration) -> Result<JudgeResult, JudgeClientError> {
+    let server = MockJudgeServer::start(Reply::Accepted).unwrap();
+    server.push(reply);
+    run_judge_client(&server.url(), "", &settings(""), timeout)
+}
+
+fn last_result(result: &JudgeResult) -> String {
+    result
+        .details
+        .as_ref()
+        .unwrap()
+        .last()
+        .unwrap()
+        .result
+        .clone()
+}
+
+// A judge server known to this process, draining ones are never chosen by
+// tests scheduling real tasks.
+fn server_info(url: &str, state: &str) -> JudgeServerInfo {
+    JudgeServerInfo {
+        judger_version: mock_judge_server::JUDGER_VERSION.to_owned(),
+        hostname: "mock-judge-server".to_owned(),
+        cpu_core: 2,
+        memory: 0.0,
+        cpu: 0.0,
+        task_number: 0,
+        service_url: url.to_owned(),
+        token: String::new(),
+        heartbeat_time: SystemTime::now(),
+        is_deprecated: false,
+        state: state.to_owned(),
+        health: Default::default(),
+        compiler_versions: Default::default(),
+    }
+}
+
+#[test]
+fn client_parses_verdicts() {
+    let timeout = Duration::from_secs(5);
+
+    let result = judge(Reply::Accepted, timeout).unwrap();
+    assert_eq!(result.is_accepted, Some(true));
+    assert_eq!(last_result(&result), "SUCCESS");
+
+    let result = judge(Reply::WrongAnswer, timeout).unwrap();
+    assert_eq!(result.is_accepted, Some(false));
+    assert_eq!(last_result(&result), "WRONG_ANSWER");
+
+    let result = judge(Reply::TimeLimitExceeded, timeout).unwrap();
+    assert_eq!(result.max_time, Some(1001));
+    assert_eq!(last_result(&result), "CPU_TIME_LIMIT_EXCEEDED");
+
+    let result = judge(Reply::SystemError, timeout).unwrap();
+    assert_eq!(last_result(&result), "SYSTEM_ERROR");
+
+    let result = judge(Reply::CompileError, timeout).unwrap();
+    assert_eq!(result.err.as_deref(), Some(COMPILE_ERROR_ERR));
+    assert_eq!(result.is_accepted, Some(false));
+    assert!(result.err_reason.is_some());
+}
+
+#[test]
+fn client_reports_transport_errors() {
+    let slow = Reply::Slow(Duration::from_secs(2), Box::new(Reply::Accepted));
+    match judge(slow, Duration::from_millis(300)) {
+        Err(JudgeClientError::Timeout) => (),
+        res => panic!("expected timeout, got {:?}", res),
+    }
+
+    assert!(judge(Reply::Dropped, Duration::from_secs(5)).is_err());
+}
+
+#[test]
+fn language_list_has_compiler_versions_of_servers() {
+    let url = format!("http://{}", Uuid::new_v4());
+    let mut compiler_versions = HashMap::new();
+    compiler_versions.insert("c".to_owned(), "gcc 9.4.0".to_owned());
+    JUDGE_SERVER_INFOS.write().unwrap().insert(
+        url.clone(),
+        JudgeServerInfo {
+            compiler_versions,
+            ..server_info(&url, judge_servers::DRAINING)
+        },
+    );
+
+    let languages = language::get_list().unwrap();
+    JUDGE_SERVER_INFOS.write().unwrap().remove(&url);
+
+    let c = languages
+        .iter()
+        .find(|language| language.id == "c")
+        .unwrap();
+    assert!(c.compile_command.contains("gcc"));
+    assert!(c.compiler_versions.contains(&"gcc 9.4.0".to_owned()));
+    assert!(languages.iter().any(|language| language.id == "py3"));
+}
+
+#[test]
+fn http_backend_refuses_interactive_problems() {
+    let backend = HttpBackend::new(scheduling::from_name("least_loaded"));
+    let mut settings = settings("int main() {}");
+    settings.interactor_config = Some(interactor_config());
+    let judge_result = backend.judge("http://nowhere", &settings, Duration::from_secs(5));
+    assert!(matches!(
+        judge_result,
+        Err(JudgeBackendError::Unsupported(_))
+    ));
+    assert_eq!(health::outcome_of(&judge_result), None);
+}
+
+#[test]
+fn users_run_one_piece_of_code_at_a_time() {
+    // no real user has a negative id
+    let running_user = submission::start_run(-1).unwrap();
+    assert!(matches!(
+        submission::start_run(-1),
+        Err(ServiceError::BadRequest(_))
+    ));
+    assert!(submission::start_run(-2).is_ok());
+    drop(running_user);
+    assert!(submission::start_run(-1).is_ok());
+}
+
+#[test]
+fn failing_server_is_quarantined_until_it_passes_probes() {
+    let server = MockJudgeServer::start(Reply::Accepted).unwrap();
+    for _ in 0..3 {
+        server.push(Reply::SystemError);
+    }
+    JUDGE_SERVER_INFOS.write().unwrap().insert(
+        server.url(),
+        server_info(&server.url(), judge_servers::DRAINING),
+    );
+    let health_of = |url: &str| JUDGE_SERVER_INFOS.read().unwrap()[url].health.clone();
+
+    let backend = HttpBackend::new(scheduling::from_name("least_loaded"));
+    let submission_ids: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+    let mut affected_ids = None;
+    for submission_id in submission_ids.iter() {
+        let judge_result = backend.judge(
+            &server.url(),
+            &settings("int main() {}"),
+            Duration::from_secs(5),
+        );
+        assert_eq!(
+            health::outcome_of(&judge_result),
+            Some(JudgeOutcome::SystemError)
+        );
+        affected_ids = health::record(
+            &server.url(),
+            health::outcome_of(&judge_result).unwrap(),
+            Some(*submission_id),
+        );
+    }
+    let mut affected_ids = affected_ids.unwrap();
+    affected_ids.sort();
+    let mut expected_ids = submission_ids.clone();
+    expected_ids.sort();
+    assert_eq!(affected_ids, expected_ids);
+    assert!(health_of(&server.url()).is_quarantined());
+
+    // still cooling down, nothing is probed
+    health::run_probes(&backend, Duration::from_secs(5));
+    assert_eq!(server.judge_count(), 3);
+
+    JUDGE_SERVER_INFOS
+        .write()
+        .unwrap()
+        .get_mut(&server.url())
+        .unwrap()
+        .health
+        .quarantined_until = Some(SystemTime::now());
+    health::run_probes(&backend, Duration::from_secs(5));
+    assert!(server.judge_count() > 3);
+    let health = health_of(&server.url());
+    assert!(!health.is_quarantined());
+    assert_eq!(health.consecutive_failures, 0);
+
+    JUDGE_SERVER_INFOS.write().unwrap().remove(&server.url());
+}
+
+#[test]
+#[ignore = "needs gcc"]
+fn local_backend_judges_inline_test_cases() {
+    let backend = LocalBackend::new(TEST_CASE_ROOT, 65534, 65534);
+    let judge = |src: &str| {
+        let mut settings = settings(src);
+        settings.test_case = Some(vec![
+            TestCase {
+                input: "1 2\n".to_owned(),
+                output: "3\n".to_owned(),
+            },
+            TestCase {
+                input: "5 7\n".to_owned(),
+                output: "12\n".to_owned(),
+            },
+        ]);
+        backend
+            .judge("local", &settings, Duration::from_secs(30))
+            .unwrap()
+    };
+
+    let result = judge(
+        "#include <stdio.h>\nint main() { int a, b; scanf(\"%d %d\", &a, &b); printf(\"%d\\n\", a + b); return 0; }",
+    );
+    assert_eq!(result.is_accepted, Some(true));
+    assert_eq!(result.details.as_ref().unwrap().len(), 2);
+
+    let result = judge(
+        "#include <stdio.h>\nint main() { int a, b; scanf(\"%d %d\", &a, &b); printf(\"%d\\n\", a - b); return 0; }",
+    );
+    assert_eq!(last_result(&result), "WRONG_ANSWER");
+
+    let result = judge("int main() { for (;;); }");
+    assert_ne!(last_result(&result), "SUCCESS");
+    assert!(last_result(&result).contains("TIME_LIMIT_EXCEEDED"));
+
+    let result = judge("int main() { return 1; }");
+    assert_eq!(last_result(&result), "RUNTIME_ERROR");
+
+    let result = judge("int main() { this is not c }");
+    assert_eq!(result.err.as_deref(), Some(COMPILE_ERROR_ERR));
+    assert!(result.err_reason.is_some());
+}
+
+#[test]
+#[ignore = "needs gcc and root"]
+fn local_backend_keeps_programs_in_their_sandbox() {
+    let backend = LocalBackend::new(TEST_CASE_ROOT, 65534, 65534);
+    let judge = |src: &str| {
+        let mut settings = settings(src);
+        settings.test_case = Some(vec![TestCase {
+            input: String::new(),
+            output: "42\n".to_owned(),
+        }]);
+        backend
+            .judge("local", &settings, Duration::from_secs(30))
+            .unwrap()
+    };
+
+    // inline answers are next to the work dir, but not readable by the sandbox user
+    let result = judge(
+        "#include <stdio.h>\n#include <string.h>\n#include <unistd.h>\nint main() { char path[4096]; getcwd(path, 4000); FILE *f = fopen(strcat(path, \".data/1.out\"), \"r\"); puts(f ? \"leaked\" : \"42\"); return 0; }",
+    );
+    assert_eq!(last_result(&result), "SUCCESS");
+
+    // nor are the answers in test data
+    let answer_path = std::fs::canonicalize(format!("{}/1/1.out", TEST_CASE_ROOT)).unwrap();
+    let result = judge(&format!(
+        "#include <stdio.h>\nint main() {{ FILE *f = fopen(\"{}\", \"r\"); puts(f ? \"leaked\" : \"42\"); return 0; }}",
+        answer_path.to_string_lossy()
+    ));
+    assert_eq!(last_result(&result), "SUCCESS");
+
+    // children left in the background are killed with the program
+    let marker = std::env::temp_dir().join(format!("shupdtp-escaped-{}", Uuid::new_v4()));
+    let result = judge(&format!(
+        "#include <stdio.h>\n#include <unistd.h>\nint main() {{ if (fork() == 0) {{ sleep(1); fclose(fopen(\"{}\", \"w\")); return 0; }} puts(\"42\"); return 0; }}",
+        marker.to_string_lossy()
+    ));
+    assert_eq!(last_result(&result), "SUCCESS");
+    std::thread::sleep(Duration::from_secs(2));
+    assert!(!marker.exists());
+}
+
+#[test]
+#[ignore = "needs gcc"]
+fn local_backend_runs_custom_input() {
+    let backend = LocalBackend::new(TEST_CASE_ROOT, 65534, 65534);
+    let mut settings = settings(
+        "#include <stdio.h>\nint main() { int a; scanf(\"%d\", &a); printf(\"%d\\n\", a * 2); fprintf(stderr, \"debug\"); return 0; }",
+    );
+    settings.test_case_id = None;
+    settings.test_case = Some(vec![TestCase {
+        input: "21\n".to_owned(),
+        output: String::new(),
+    }]);
+    settings.output = true;
+
+    let result = RunResult::from(
+        backend
+            .judge("local", &settings, Duration::from_secs(30))
+            .unwrap(),
+    );
+    assert_eq!(result.result.as_deref(), Some("SUCCESS"));
+    assert_eq!(result.stdout.as_deref(), Some("42\n"));
+    assert_eq!(result.stderr.as_deref(), Some("debug"));
+    assert_eq!(result.exit_code, Some(0));
+}
+
+// accepts any sum which equals the answer, leading zeros included
+const CHECKER_SRC: &str = r#"import sys
+user = open(sys.argv[2]).read().split()
+answer = open(sys.argv[3]).read().split()
+sys.exit(0 if len(user) == 1 and int(user[0]) == int(answer[0]) else 1)
+"#;
+
+#[test]
+#[ignore = "needs gcc and python3"]
+fn local_backend_runs_checkers() {
+    let root = std::env::temp_dir().join(format!("shupdtp-test-{}", Uuid::new_v4()));
+    let test_case_dir = root.join("checker");
+    std::fs::create_dir_all(&test_case_dir).unwrap();
+    std::fs::write(test_case_dir.join("1.in"), "1 2\n").unwrap();
+    std::fs::write(test_case_dir.join("1.out"), "3\n").unwrap();
+    std::fs::write(test_case_dir.join("checker.py"), CHECKER_SRC).unwrap();
+    std::fs::write(
+        test_case_dir.join("info"),
+        r#"{"test_cases": {"1": {"input_name": "1.in", "output_name": "1.out"}}}"#,
+    )
+    .unwrap();
+
+    let backend = LocalBackend::new(&root.to_string_lossy(), 65534, 65534);
+    let judge = |src: &str| {
+        let mut settings = settings(src);
+        settings.test_case_id = Some("checker".to_owned());
+        settings.checker = Some(checker_config("py3"));
+        backend
+            .judge("local", &settings, Duration::from_secs(30))
+            .unwrap()
+    };
+
+    let result = judge("#include <stdio.h>\nint main() { printf(\"003\\n\"); return 0; }");
+    assert_eq!(result.is_accepted, Some(true));
+
+    let result = judge("#include <stdio.h>\nint main() { printf(\"4\\n\"); return 0; }");
+    assert_eq!(last_result(&result), "WRONG_ANSWER");
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn empty_helper_commands_are_errors() {
+    let dir = std::env::temp_dir();
+    let checker = CheckerConfig {
+        language: "c".to_owned(),
+        command: " ".to_owned(),
+    };
+    let verdict = run_checker(
+        &checker,
+        &dir,
+        &dir.join("1.in"),
+        &dir.join("1.user_out"),
+        &dir.join("1.out"),
+        Duration::from_secs(1),
+    );
+    assert_eq!(
+        verdict.unwrap_err().kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+
+    assert!(matches!(
+        run_with_timeout("", &dir, None, Duration::from_secs(1)),
+        Err(ServiceError::InternalServerErrorWithHint(_))
+    ));
+}
+
+const INTERACTOR_SRC: &str = r#"#include <cstdio>
+int main(int argc, char **argv) {
+    FILE *in = fopen(argv[1], "r"), *out = fopen(argv[2], "w");
+    int a, b, c;
+    fscanf(in, "%d %d", &a, &b);
+    printf("%d %d\n", a, b);
+    fflush(stdout);
+    if (scanf("%d", &c) != 1) return 1;
+    fprintf(out, "%d\n", c);
+    return c == a + b ? 0 : 1;
+}
+"#;
+
+// writes only after programs which do not read have surely exited
+const SLOW_INTERACTOR_SRC: &str = r#"#include <cstdio>
+#include <unistd.h>
+int main(int argc, char **argv) {
+    FILE *in = fopen(argv[1], "r");
+    int a, b, c;
+    fscanf(in, "%d %d", &a, &b);
+    usleep(300000);
+    printf("%d %d\n", a, b);
+    fflush(stdout);
+    if (scanf("%d", &c) != 1) return 1;
+    return c == a + b ? 0 : 1;
+}
+"#;
+
+#[test]
+#[ignore = "needs g++"]
+fn local_backend_judges_interactive_problems() {
+    let backend = LocalBackend::new(TEST_CASE_ROOT, 65534, 65534);
+    let judge = |src: &str, interactor_src: &str| {
+        let mut settings = settings(src);
+        settings.test_case = Some(vec![
+            TestCase {
+                input: "1 2\n".to_owned(),
+                output: String::new(),
+            },
+            TestCase {
+                input: "5 7\n".to_owned(),
+                output: String::new(),
+            },
+        ]);
+        settings.interactor_config = Some(interactor_config());
+        settings.interactor_compile_config = Some(interactor_compile_config());
+        settings.interactor_src = Some(interactor_src.to_owned());
+        settings.output = true;
+        backend
+            .judge("local", &settings, Duration::from_secs(30))
+            .unwrap()
+    };
+
+    let result = judge(
+        "#include <stdio.h>\nint main() { int a, b; scanf(\"%d %d\", &a, &b); printf(\"%d\\n\", a + b); return 0; }",
+        INTERACTOR_SRC,
+    );
+    assert_eq!(result.is_accepted, Some(true));
+    assert_eq!(
+        result.details.as_ref().unwrap()[1].output.as_deref(),
+        Some("12\n")
+    );
+
+    let result = judge(
+        "#include <stdio.h>\nint main() { int a, b; scanf(\"%d %d\", &a, &b); printf(\"%d\\n\", a - b); return 0; }",
+        INTERACTOR_SRC,
+    );
+    assert_eq!(last_result(&result), "WRONG_ANSWER");
+
+    // the interactor gets EOF when the program exits without answering
+    let result = judge("int main() { return 0; }", INTERACTOR_SRC);
+    assert_eq!(last_result(&result), "WRONG_ANSWER");
+
+    // or fails to write, when the program has exited before the interactor wrote anything
+    let result = judge("int main() { return 0; }", SLOW_INTERACTOR_SRC);
+    assert_eq!(last_result(&result), "WRONG_ANSWER");
+
+    let result = judge("int main() { for (;;); }", INTERACTOR_SRC);
+    assert!(last_result(&result).contains("TIME_LIMIT_EXCEEDED"));
+
+    let result = judge("int main() { return 0; }", "this is not c++");
+    assert_eq!(result.err.as_deref(), Some("InteractorCompileError"));
+}
+
+// A released problem with test case count and limits set, `settings` is laid over them.
+fn insert_problem(conn: &PgConnection, title: &str, settings: serde_json::Value) -> i32 {
+    use crate::schema::problems as problems_schema;
+
+    let mut problem_settings = serde_json::json!({
+        "is_spj": false,
+        "max_cpu_time": 1000,
+        "max_memory": 256 * 1024 * 1024,
+        "opaque_output": false,
+        "test_case_count": 1,
+    });
+    for (key, value) in settings.as_object().unwrap() {
+        problem_settings[key] = value.clone();
+    }
+    diesel::insert_into(problems_schema::table)
+        .values(&InsertableProblem {
+            title: format!("{}-{}", title, Uuid::new_v4()),
+            tags: Vec::new(),
+            difficulty: 0.0,
+            contents: r#"{"description": null, "example_count": 0, "examples": []}"#.to_owned(),
+            settings: problem_settings.to_string(),
+            is_released: true,
+        })
+        .returning(problems_schema::id)
+        .get_result(conn)
+        .unwrap()
+}
+
+// One actor judging on `backend`, giving up on the first failure.
+// Has to be started inside a running system.
+fn start_actor(
+    pool: Pool,
+    judge_timeout: Duration,
+    backend: Arc<dyn JudgeBackend>,
+) -> Addr<JudgeActor> {
+    SyncArbiter::start(1, move || JudgeActor {
+        pool: pool.clone(),
+        judge_timeout,
+        starvation_timeout: chrono::Duration::seconds(60),
+        max_attempts: 1,
+        retry_backoff: chrono::Duration::seconds(1),
+        backend: backend.clone(),
+    })
+}
+
+fn insert_submission(conn: &PgConnection, src: &str) -> Uuid {
+    use crate::schema::submissions as submissions_schema;
+
+    let id = Uuid::new_v4();
+    diesel::insert_into(submissions_schema::table)
+        .values(&InsertableSubmission {
+            id,
+            problem_id: 0,
+            user_id: 0,
+            region: None,
+            state: "Waiting".to_owned(),
+            settings: serde_json::to_string(&settings(src)).unwrap(),
+            result: None,
+            submit_time: get_cur_naive_date_time(),
+            is_accepted: None,
+            finish_time: None,
+            max_time: None,
+            max_memory: None,
+            language: Some("c".to_owned()),
+            err: None,
+            content_hash: None,
+            is_cached: false,
+        })
+        .execute(conn)
+        .unwrap();
+    queue::push(conn, id, SAMPLE_LANE).unwrap();
+    id
+}
+
+// Tests putting claimable tasks into the shared queue take turns,
+// so a queue drained by one of them is not judging tasks of the others.
+static QUEUE_LOCK: Mutex<()> = Mutex::new(());
+
+fn lock_queue() -> MutexGuard<'static, ()> {
+    QUEUE_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// A backend without capacity, for actors which must leave the queue alone.
+struct IdleBackend;
+
+impl JudgeBackend for IdleBackend {
+    fn name(&self) -> &'static str {
+        "idle"
+    }
+
+    fn choose_target(&self) -> Option<String> {
+        None
+    }
+
+    fn judge(
+        &self,
+        target: &str,
+        _settings: &JudgeSettings,
+        _timeout: Duration,
+    ) -> Result<JudgeResult, JudgeBackendError> {
+        unreachable!("idle backend was asked to judge on {}", target)
+    }
+}
+
+#[test]
+#[ignore = "needs a database"]
+fn expired_leases_are_released() {
+    dotenv::dotenv().ok();
+    let database_url = dotenv::var("DATABASE_URL").unwrap();
+    let pool = server_core::database::pool::establish_connection_with_count(&database_url, 1);
+    let conn = server_core::database::db_connection(&pool).unwrap();
+
+    use crate::schema::judge_queue as judge_queue_schema;
+    use crate::schema::submissions as submissions_schema;
+
+    // left Pending and leased by an actor which failed, backing off so no other test claims it
+    let id = insert_submission(&conn, "// mock:ac");
+    diesel::update(submissions_schema::table.filter(submissions_schema::id.eq(id)))
+        .set(submissions_schema::state.eq("Pending".to_owned()))
+        .execute(&conn)
+        .unwrap();
+    diesel::update(judge_queue_schema::table.filter(judge_queue_schema::submission_id.eq(id)))
+        .set((
+            judge_queue_schema::lease_time
+                .eq(Some(get_cur_naive_date_time() - chrono::Duration::hours(1))),
+            judge_queue_schema::retry_time
+                .eq(Some(get_cur_naive_date_time() + chrono::Duration::hours(1))),
+        ))
+        .execute(&conn)
+        .unwrap();
+
+    let released_ids = queue::release_expired(&conn, chrono::Duration::minutes(5)).unwrap();
+    assert!(released_ids.contains(&id));
+
+    let lease_time: Option<chrono::NaiveDateTime> = judge_queue_schema::table
+        .filter(judge_queue_schema::submission_id.eq(id))
+        .select(judge_queue_schema::lease_time)
+        .first(&conn)
+        .unwrap();
+    assert_eq!(lease_time, None);
+    let state: String = submissions_schema::table
+        .filter(submissions_schema::id.eq(id))
+        .select(submissions_schema::state)
+        .first(&conn)
+        .unwrap();
+    assert_eq!(state, "Waiting");
+
+    queue::remove(&conn, id).unwrap();
+    diesel::delete(submissions_schema::table.filter(submissions_schema::id.eq(id)))
+        .execute(&conn)
+        .unwrap();
+}
+
+#[test]
+#[ignore = "needs a database"]
+fn start_judge_runs_queue_against_mock_server() {
+    let _queue_lock = lock_queue();
+    dotenv::dotenv().ok();
+    let database_url = dotenv::var("DATABASE_URL").unwrap();
+    let pool = server_core::database::pool::establish_connection_with_count(&database_url, 2);
+    let conn = server_core::database::db_connection(&pool).unwrap();
+
+    let server = MockJudgeServer::start(Reply::Accepted).unwrap();
+    JUDGE_SERVER_INFOS.write().unwrap().insert(
+        server.url(),
+        server_info(&server.url(), judge_servers::ACTIVE),
+    );
+
+    let accepted_id = insert_submission(&conn, "// mock:ac");
+    let wrong_answer_id = insert_submission(&conn, "// mock:wa");
+    let compile_error_id = insert_submission(&conn, "// mock:ce");
+    let dropped_id = insert_submission(&conn, "// mock:drop");
+
+    let actor_pool = pool.clone();
+    System::new("judge-actor-test").block_on(async move {
+        let addr = start_actor(
+            actor_pool,
+            Duration::from_secs(5),
+            Arc::new(HttpBackend::new(scheduling::from_name("least_loaded"))),
+        );
+        addr.send(StartJudge()).await.unwrap();
+    });
+
+    use crate::schema::submissions as submissions_schema;
+    let load = |id: Uuid| -> RawSubmission {
+        submissions_schema::table
+            .filter(submissions_schema::id.eq(id))
+            .first(&conn)
+            .unwrap()
+    };
+
+    let accepted = load(accepted_id);
+    assert_eq!(accepted.state, "Finished");
+    assert_eq!(accepted.is_accepted, Some(true));
+    assert_eq!(accepted.score, Some(100));
+
+    let wrong_answer = load(wrong_answer_id);
+    assert_eq!(wrong_answer.state, "Finished");
+    assert_eq!(wrong_answer.is_accepted, Some(false));
+    assert_eq!(wrong_answer.score, Some(50));
+
+    let compile_error = load(compile_error_id);
+    assert_eq!(compile_error.state, "Finished");
+    assert_eq!(compile_error.err.as_deref(), Some(COMPILE_ERROR_ERR));
+    assert_eq!(compile_error.is_accepted, Some(false));
+    assert_eq!(
+        compile_error.out_results,
+        Some(vec![COMPILE_ERROR.to_owned()])
+    );
+    assert_eq!(compile_error.score, Some(0));
+
+    let dropped = load(dropped_id);
+    assert_eq!(dropped.state, "SystemError");
+    assert!(dropped.err.is_some());
+
+    assert_eq!(server.judge_count(), 4);
+
+    diesel::delete(
+        submissions_schema::table.filter(submissions_schema::id.eq_any(vec![
+            accepted_id,
+            wrong_answer_id,
+            compile_error_id,
+            dropped_id,
+        ])),
+    )
+    .execute(&conn)
+    .unwrap();
+    JUDGE_SERVER_INFOS.write().unwrap().remove(&server.url());
+}
+
+#[test]
+#[ignore = "needs a database and gcc"]
+fn start_hack_changes_verdict_of_target() {
+    dotenv::dotenv().ok();
+    let database_url = dotenv::var("DATABASE_URL").unwrap();
+    let pool = server_core::database::pool::establish_connection_with_count(&database_url, 2);
+    let conn = server_core::database::db_connection(&pool).unwrap();
+
+    use crate::schema::hacks as hacks_schema;
+    use crate::schema::problems as problems_schema;
+    use crate::schema::submissions as submissions_schema;
+
+    let problem_id = insert_problem(
+        &conn,
+        "hack-test",
+        serde_json::json!({
+            "test_case_count": 0,
+            "std_language": "c",
+        }),
+    );
+    let test_case_dir = test_case_dir(problem_id);
+    // ids start over in a fresh database, test data checked in must not be removed below
+    assert!(
+        !std::path::Path::new(&test_case_dir).exists(),
+        "{} already exists",
+        test_case_dir
+    );
+    std::fs::create_dir_all(&test_case_dir).unwrap();
+    std::fs::write(
+        format!("{}/std.c", test_case_dir),
+        "#include <stdio.h>\nint main() { int a, b; scanf(\"%d %d\", &a, &b); printf(\"%d\\n\", a + b); return 0; }",
+    )
+    .unwrap();
+
+    // wrong when the first number is 0
+    let mut target_settings = settings(
+        "#include <stdio.h>\nint main() { int a, b; scanf(\"%d %d\", &a, &b); printf(\"%d\\n\", a ? a + b : 0); return 0; }",
+    );
+    target_settings.test_case_id = Some(problem_id.to_string());
+    let target_id = Uuid::new_v4();
+    diesel::insert_into(submissions_schema::table)
+        .values(&InsertableSubmission {
+            id: target_id,
+            problem_id,
+            user_id: 1,
+            region: Some("hack-test".to_owned()),
+            state: "Finished".to_owned(),
+            settings: serde_json::to_string(&target_settings).unwrap(),
+            result: Some(r#"{"err": null, "err_reason": null, "is_accepted": true, "max_time": 0, "max_memory": 0, "details": []}"#.to_owned()),
+            submit_time: get_cur_naive_date_time(),
+            is_accepted: Some(true),
+            finish_time: None,
+            max_time: None,
+            max_memory: None,
+            language: Some("c".to_owned()),
+            err: None,
+            content_hash: None,
+            is_cached: false,
+        })
+        .execute(&conn)
+        .unwrap();
+
+    let insert_hack = |input: &str| -> Uuid {
+        let id = Uuid::new_v4();
+        diesel::insert_into(hacks_schema::table)
+            .values(&Hack {
+                id,
+                submission_id: target_id,
+                region: "hack-test".to_owned(),
+                problem_id,
+                hacker_id: 2,
+                defender_id: 1,
+                input: input.to_owned(),
+                answer: None,
+                state: hacks::WAITING.to_owned(),
+                reason: None,
+                create_time: get_cur_naive_date_time(),
+                finish_time: None,
+                test_case_id: None,
+            })
+            .execute(&conn)
+            .unwrap();
+        id
+    };
+    let unsuccessful_id = insert_hack("1 2\n");
+    let successful_id = insert_hack("0 5\n");
+
+    let actor_pool = pool.clone();
+    System::new("hack-test").block_on(async move {
+        let addr = start_actor(
+            actor_pool,
+            Duration::from_secs(30),
+            Arc::new(LocalBackend::new(TEST_CASE_ROOT, 65534, 65534)),
+        );
+        addr.send(StartHack(unsuccessful_id)).await.unwrap();
+        addr.send(StartHack(successful_id)).await.unwrap();
+    });
+
+    let load_hack = |id: Uuid| -> Hack {
+        hacks_schema::table
+            .filter(hacks_schema::id.eq(id))
+            .first(&conn)
+            .unwrap()
+    };
+    let unsuccessful = load_hack(unsuccessful_id);
+    assert_eq!(unsuccessful.state, hacks::UNSUCCESSFUL);
+    assert_eq!(unsuccessful.answer.as_deref(), Some("3\n"));
+    let successful = load_hack(successful_id);
+    assert_eq!(successful.state, hacks::SUCCESSFUL);
+    assert_eq!(successful.answer.as_deref(), Some("5\n"));
+
+    let target: RawSubmission = submissions_schema::table
+        .filter(submissions_schema::id.eq(target_id))
+        .first(&conn)
+        .unwrap();
+    assert_eq!(target.is_accepted, Some(false));
+    assert_eq!(target.score, Some(0));
+    assert_eq!(target.out_results, Some(vec!["WRONG_ANSWER".to_owned()]));
+
+    diesel::delete(submissions_schema::table.filter(submissions_schema::id.eq(target_id)))
+        .execute(&conn)
+        .unwrap();
+    diesel::delete(problems_schema::table.filter(problems_schema::id.eq(problem_id)))
+        .execute(&conn)
+        .unwrap();
+    std::fs::remove_dir_all(&test_case_dir).unwrap();
+}
+
+// A backend whose only target never answers, counting how often it was asked.
+struct UnreachableBackend(Arc<Mutex<i32>>);
+
+impl JudgeBackend for UnreachableBackend {
+    fn name(&self) -> &'static str {
+        "unreachable"
+    }
+
+    fn choose_target(&self) -> Option<String> {
+        Some("unreachable".to_owned())
+    }
+
+    fn judge(
+        &self,
+        target: &str,
+        _settings: &JudgeSettings,
+        _timeout: Duration,
+    ) -> Result<JudgeResult, JudgeBackendError> {
+        *self.0.lock().unwrap() += 1;
+        Err(JudgeBackendError::Client(
+            JudgeClientError::ConnectionRefused(target.to_owned()),
+        ))
+    }
+}
+
+#[test]
+#[ignore = "needs a database"]
+fn hack_is_retried_when_the_backend_fails() {
+    dotenv::dotenv().ok();
+    let database_url = dotenv::var("DATABASE_URL").unwrap();
+    let pool = server_core::database::pool::establish_connection_with_count(&database_url, 3);
+    let conn = server_core::database::db_connection(&pool).unwrap();
+
+    use crate::schema::hacks as hacks_schema;
+    use crate::schema::problems as problems_schema;
+    use crate::schema::submissions as submissions_schema;
+
+    let problem_id = insert_problem(
+        &conn,
+        "hack-retry-test",
+        serde_json::json!({
+            "test_case_count": 0,
+            "std_language": "c",
+        }),
+    );
+    let test_case_dir = test_case_dir(problem_id);
+    assert!(
+        !std::path::Path::new(&test_case_dir).exists(),
+        "{} already exists",
+        test_case_dir
+    );
+    std::fs::create_dir_all(&test_case_dir).unwrap();
+    std::fs::write(
+        format!("{}/std.c", test_case_dir),
+        "int main() { return 0; }",
+    )
+    .unwrap();
+
+    let target_id = Uuid::new_v4();
+    diesel::insert_into(submissions_schema::table)
+        .values(&InsertableSubmission {
+            id: target_id,
+            problem_id,
+            user_id: 1,
+            region: Some("hack-retry-test".to_owned()),
+            state: "Finished".to_owned(),
+            settings: serde_json::to_string(&settings("int main() { return 0; }")).unwrap(),
+            result: None,
+            submit_time: get_cur_naive_date_time(),
+            is_accepted: Some(true),
+            finish_time: None,
+            max_time: None,
+            max_memory: None,
+            language: Some("c".to_owned()),
+            err: None,
+            content_hash: None,
+            is_cached: false,
+        })
+        .execute(&conn)
+        .unwrap();
+    let hack_id = Uuid::new_v4();
+    diesel::insert_into(hacks_schema::table)
+        .values(&Hack {
+            id: hack_id,
+            submission_id: target_id,
+            region: "hack-retry-test".to_owned(),
+            problem_id,
+            hacker_id: 2,
+            defender_id: 1,
+            input: "1 2\n".to_owned(),
+            answer: None,
+            state: hacks::WAITING.to_owned(),
+            reason: None,
+            create_time: get_cur_naive_date_time(),
+            finish_time: None,
+            test_case_id: None,
+        })
+        .execute(&conn)
+        .unwrap();
+
+    let judged = Arc::new(Mutex::new(0));
+    let backend_judged = judged.clone();
+    let actor_pool = pool.clone();
+    let poll_pool = pool.clone();
+    let hack: Hack = System::new("hack-retry-test").block_on(async move {
+        let addr = SyncArbiter::start(1, move || JudgeActor {
+            pool: actor_pool.clone(),
+            judge_timeout: Duration::from_secs(1),
+            starvation_timeout: chrono::Duration::seconds(60),
+            max_attempts: 3,
+            retry_backoff: chrono::Duration::milliseconds(10),
+            backend: Arc::new(UnreachableBackend(backend_judged.clone())),
+        });
+        addr.send(StartHack(hack_id)).await.unwrap();
+        // retries are sent from other threads
+        let conn = server_core::database::db_connection(&poll_pool).unwrap();
+        let deadline = SystemTime::now() + Duration::from_secs(10);
+        loop {
+            let hack: Hack = hacks_schema::table
+                .filter(hacks_schema::id.eq(hack_id))
+                .first(&conn)
+                .unwrap();
+            if hack.state != hacks::WAITING || SystemTime::now() > deadline {
+                break hack;
+            }
+            actix::clock::delay_for(Duration::from_millis(20)).await;
+        }
+    });
+
+    assert_eq!(hack.state, hacks::FAILED);
+    assert!(hack
+        .reason
+        .as_deref()
+        .unwrap_or_default()
+        .starts_with("Judge failed 3 times"));
+    assert_eq!(*judged.lock().unwrap(), 3);
+
+    diesel::delete(submissions_schema::table.filter(submissions_schema::id.eq(target_id)))
+        .execute(&conn)
+        .unwrap();
+    diesel::delete(problems_schema::table.filter(problems_schema::id.eq(problem_id)))
+        .execute(&conn)
+        .unwrap();
+    std::fs::remove_dir_all(&test_case_dir).unwrap();
+}
+
+#[test]
+#[ignore = "needs a database"]
+fn identical_submission_reuses_finished_result() {
+    dotenv::dotenv().ok();
+    let database_url = dotenv::var("DATABASE_URL").unwrap();
+    let pool = server_core::database::pool::establish_connection_with_count(&database_url, 2);
+    let conn = server_core::database::db_connection(&pool).unwrap();
+
+    use crate::schema::problems as problems_schema;
+    use crate::schema::submissions as submissions_schema;
+
+    let problem_id = insert_problem(
+        &conn,
+        "cache-test",
+        serde_json::json!({
+            "test_data_version": "v1",
+        }),
+    );
+
+    let src = "int main() { return 0; }";
+    let judge_settings =
+        submission::build_settings(&conn, problem_id, src.to_owned(), "c").unwrap();
+    let finished_id = Uuid::new_v4();
+    diesel::insert_into(submissions_schema::table)
+        .values(&InsertableSubmission {
+            id: finished_id,
+            problem_id,
+            user_id: 1,
+            region: None,
+            state: "Finished".to_owned(),
+            settings: serde_json::to_string(&judge_settings).unwrap(),
+            result: Some(r#"{"err": null, "err_reason": null, "is_accepted": true, "max_time": 3, "max_memory": 1024, "details": [{"cpu_time": 3, "real_time": 3, "memory": 1024, "signal": 0, "exit_code": 0, "error": "SUCCESS", "result": "SUCCESS", "test_case": "1", "output_md5": null, "output": null}]}"#.to_owned()),
+            submit_time: get_cur_naive_date_time(),
+            is_accepted: Some(true),
+            finish_time: Some(get_cur_naive_date_time()),
+            max_time: Some(3),
+            max_memory: Some(1024),
+            language: Some("c".to_owned()),
+            err: None,
+            content_hash: submission::content_hash(&conn, problem_id, &judge_settings).unwrap(),
+            is_cached: false,
+        })
+        .execute(&conn)
+        .unwrap();
+
+    let actor_pool = pool.clone();
+    let service_pool = pool.clone();
+    let cached_id = System::new("cache-test").block_on(async move {
+        let addr = start_actor(
+            actor_pool,
+            Duration::from_secs(5),
+            Arc::new(LocalBackend::new(TEST_CASE_ROOT, 65534, 65534)),
+        );
+        submission::create(
+            None,
+            problem_id,
+            2,
+            src.to_owned(),
+            "c".to_owned(),
+            actix_web::web::Data::new(service_pool),
+            actix_web::web::Data::new(super::JudgeActorAddr { addr }),
+        )
+        .unwrap()
+    });
+
+    let cached: RawSubmission = submissions_schema::table
+        .filter(submissions_schema::id.eq(cached_id))
+        .first(&conn)
+        .unwrap();
+    assert_eq!(cached.state, "Finished");
+    assert!(cached.is_cached);
+    assert_eq!(cached.is_accepted, Some(true));
+    assert_eq!(cached.max_time, Some(3));
+    assert_eq!(cached.score, Some(100));
+    assert_eq!(cached.out_results, Some(vec!["SUCCESS".to_owned()]));
+    assert!(!queue::waiting_ids(&conn).unwrap().contains(&cached_id));
+
+    diesel::delete(
+        submissions_schema::table
+            .filter(submissions_schema::id.eq_any(vec![finished_id, cached_id])),
+    )
+    .execute(&conn)
+    .unwrap();
+    diesel::delete(problems_schema::table.filter(problems_schema::id.eq(problem_id)))
+        .execute(&conn)
+        .unwrap();
+}
+
+#[test]
+#[ignore = "needs a database"]
+fn region_rejects_languages_it_does_not_allow() {
+    dotenv::dotenv().ok();
+    let database_url = dotenv::var("DATABASE_URL").unwrap();
+    let pool = server_core::database::pool::establish_connection_with_count(&database_url, 2);
+    let conn = server_core::database::db_connection(&pool).unwrap();
+
+    use crate::schema::problems as problems_schema;
+    use crate::schema::region_links as region_links_schema;
+    use crate::schema::regions as regions_schema;
+
+    let region_name = format!("intro-c-{}", Uuid::new_v4());
+    diesel::insert_into(regions_schema::table)
+        .values(&Region {
+            name: region_name.clone(),
+            self_type: "problem_set".to_owned(),
+            title: "Introduction to C".to_owned(),
+            has_access_setting: false,
+            introduction: None,
+            allowed_languages: None,
+        })
+        .execute(&conn)
+        .unwrap();
+    let problem_id = insert_problem(&conn, "region-language-test", serde_json::json!({}));
+    diesel::insert_into(region_links_schema::table)
+        .values(&RegionLink {
+            region: region_name.clone(),
+            inner_id: 1,
+            problem_id,
+            score: None,
+        })
+        .execute(&conn)
+        .unwrap();
+
+    let data_pool = actix_web::web::Data::new(pool.clone());
+    match region::set_allowed_languages(
+        region_name.clone(),
+        Some(vec!["c".to_owned(), "brainfuck".to_owned()]),
+        data_pool.clone(),
+    ) {
+        Err(ServiceError::BadRequest(_)) => (),
+        res => panic!("expected BadRequest, got {:?}", res),
+    }
+    region::set_allowed_languages(
+        region_name.clone(),
+        Some(vec!["c".to_owned()]),
+        data_pool.clone(),
+    )
+    .unwrap();
+
+    let actor_pool = pool.clone();
+    let service_pool = data_pool.clone();
+    let service_region = region_name.clone();
+    let res = System::new("region-language-test").block_on(async move {
+        let addr = start_actor(
+            actor_pool,
+            Duration::from_secs(5),
+            Arc::new(LocalBackend::new(TEST_CASE_ROOT, 65534, 65534)),
+        );
+        region::create_submission(
+            service_region,
+            1,
+            2,
+            "print(1)".to_owned(),
+            "py3".to_owned(),
+            service_pool,
+            actix_web::web::Data::new(super::JudgeActorAddr { addr }),
+        )
+    });
+    match res {
+        Err(ServiceError::BadRequest(hint)) => assert!(hint.contains("not allowed")),
+        res => panic!("expected BadRequest, got {:?}", res),
+    }
+    region::utils::check_language_allowed(region_name.clone(), "c", &conn).unwrap();
+
+    region::set_allowed_languages(region_name.clone(), None, data_pool).unwrap();
+    region::utils::check_language_allowed(region_name.clone(), "py3", &conn).unwrap();
+
+    diesel::delete(region_links_schema::table.filter(region_links_schema::region.eq(&region_name)))
+        .execute(&conn)
+        .unwrap();
+    diesel::delete(regions_schema::table.filter(regions_schema::name.eq(&region_name)))
+        .execute(&conn)
+        .unwrap();
+    diesel::delete(problems_schema::table.filter(problems_schema::id.eq(problem_id)))
+        .execute(&conn)
+        .unwrap();
+}
+
+#[test]
+#[ignore = "needs a database"]
+fn rejudge_skips_submissions_which_no_longer_resolve() {
+    let _queue_lock = lock_queue();
+    dotenv::dotenv().ok();
+    let database_url = dotenv::var("DATABASE_URL").unwrap();
+    let pool = server_core::database::pool::establish_connection_with_count(&database_url, 2);
+    let conn = server_core::database::db_connection(&pool).unwrap();
+
+    use crate::schema::problems as problems_schema;
+    use crate::schema::rejudge_records as rejudge_records_schema;
+    use crate::schema::submissions as submissions_schema;
+
+    let problem_id = insert_problem(&conn, "rejudge-test", serde_json::json!({}));
+
+    // the second one is written in a language removed from languages.toml since
+    let mut ids = Vec::new();
+    for language in ["c", "brainfuck"].iter() {
+        let id = Uuid::new_v4();
+        diesel::insert_into(submissions_schema::table)
+            .values(&InsertableSubmission {
+                id,
+                problem_id,
+                user_id: 1,
+                region: None,
+                state: "Finished".to_owned(),
+                settings: serde_json::to_string(&settings("int main() { return 0; }")).unwrap(),
+                result: None,
+                submit_time: get_cur_naive_date_time(),
+                is_accepted: Some(true),
+                finish_time: Some(get_cur_naive_date_time()),
+                max_time: None,
+                max_memory: None,
+                language: Some(language.to_string()),
+                err: None,
+                content_hash: None,
+                is_cached: false,
+            })
+            .execute(&conn)
+            .unwrap();
+        ids.push(id);
+    }
+
+    let actor_pool = pool.clone();
+    let service_pool = pool.clone();
+    let report = System::new("rejudge-test").block_on(async move {
+        let addr = start_actor(actor_pool, Duration::from_secs(5), Arc::new(IdleBackend));
+        rejudge::create(
+            None,
+            Some(problem_id),
+            None,
+            actix_web::web::Data::new(service_pool),
+            actix_web::web::Data::new(super::JudgeActorAddr { addr }),
+        )
+        .unwrap()
+    });
+
+    assert_eq!(report.total, 1);
+    assert_eq!(report.skipped.len(), 1);
+    assert_eq!(report.skipped[0].submission_id, ids[1]);
+    assert!(queue::waiting_ids(&conn).unwrap().contains(&ids[0]));
+    assert!(!queue::waiting_ids(&conn).unwrap().contains(&ids[1]));
+
+    for id in ids.iter() {
+        queue::remove(&conn, *id).unwrap();
+    }
+    diesel::delete(
+        rejudge_records_schema::table.filter(rejudge_records_schema::batch_id.eq(report.batch_id)),
+    )
+    .execute(&conn)
+    .unwrap();
+    diesel::delete(submissions_schema::table.filter(submissions_schema::id.eq_any(ids)))
+        .execute(&conn)
+        .unwrap();
+    diesel::delete(problems_schema::table.filter(problems_schema::id.eq(problem_id)))
+        .execute(&conn)
+        .unwrap();
+}
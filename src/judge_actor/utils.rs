@@ -1,44 +1,63 @@
+use super::scheduling::SchedulingStrategy;
+use crate::models::judge_servers;
+use crate::models::problems::TEST_CASE_ROOT;
 use crate::statics::JUDGE_SERVER_INFOS;
-use std::io::{BufRead, BufReader, Write};
-use std::process::{Command, Stdio};
+use std::collections::HashMap;
+use std::fs;
 
-pub fn choose_judge_server() -> Option<(String, String)> {
+pub fn choose_judge_server(strategy: &dyn SchedulingStrategy) -> Option<(String, String)> {
     let lock = JUDGE_SERVER_INFOS.read().unwrap();
-    for (url, info) in lock.iter() {
+    let mut candidates = Vec::new();
+    for info in lock.values() {
         let last_heartbeat = info.heartbeat_time.elapsed().unwrap().as_secs() as i32;
-        if !info.is_deprecated && info.task_number + 1 <= info.cpu_core * 2 && last_heartbeat <= 5 {
-            return Some((url.to_owned(), info.token.clone()));
+        if info.state == judge_servers::ACTIVE
+            && !info.is_deprecated
+            && !info.health.is_quarantined()
+            && info.task_number < info.cpu_core * 2
+            && last_heartbeat <= 5
+        {
+            candidates.push(info);
         }
     }
-    None
+    candidates.sort_by(|a, b| a.service_url.cmp(&b.service_url));
+
+    let chosen = strategy.choose(&candidates)?;
+    info!(
+        "{} chose {} among {} servers (task_number: {}, cpu_core: {}, cpu: {})",
+        strategy.name(),
+        chosen.service_url,
+        candidates.len(),
+        chosen.task_number,
+        chosen.cpu_core,
+        chosen.cpu
+    );
+    Some((chosen.service_url.clone(), chosen.token.clone()))
 }
 
-pub fn run_judge_client(token: String, url: String, judge_setting: String) -> String {
-    // 启动子进程
-    let mut p = Command::new("./executable/send_judge_request")
-        .stdin(Stdio::piped()) // 将子进程的标准输入重定向到管道
-        .stdout(Stdio::piped()) // 将子进程的标准输出重定向到管道
-        .spawn()
-        .unwrap();
-
-    let p_stdin = p.stdin.as_mut().unwrap();
-    let mut p_stdout = BufReader::new(p.stdout.as_mut().unwrap());
-    let mut line = String::new();
-
-    p_stdin.write(token.as_bytes()).unwrap();
-    p_stdin.write("\n".as_bytes()).unwrap(); // 发送\n，子进程的read_line才会响应
-
-    p_stdin.write(url.as_bytes()).unwrap();
-    p_stdin.write("\n".as_bytes()).unwrap();
-
-    p_stdin.write(judge_setting.as_bytes()).unwrap();
-    p_stdin.write("\n".as_bytes()).unwrap();
+#[derive(Deserialize)]
+struct TestCaseWeight {
+    score: Option<i32>,
+}
 
-    // 接收消息
-    line.clear(); // 需要清空，否则会保留上次的结果
-    p_stdout.read_line(&mut line).unwrap();
-    // 等待子进程结束
-    p.wait().unwrap();
+#[derive(Deserialize)]
+struct TestCaseWeights {
+    test_cases: HashMap<String, TestCaseWeight>,
+}
 
-    line.trim().to_owned()
+// Weights written into the test case info on import, old test data without them
+// gives an empty map so every test case weighs the same.
+pub fn read_test_case_weights(test_case_id: &str) -> HashMap<String, i32> {
+    let info = match fs::read_to_string(format!("{}/{}/info", TEST_CASE_ROOT, test_case_id)) {
+        Ok(info) => info,
+        Err(_) => return HashMap::new(),
+    };
+
+    match serde_json::from_str::<TestCaseWeights>(&info) {
+        Ok(info) => info
+            .test_cases
+            .into_iter()
+            .filter_map(|(id, test_case)| test_case.score.map(|score| (id, score)))
+            .collect(),
+        Err(_) => HashMap::new(),
+    }
 }
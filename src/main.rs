@@ -10,6 +10,7 @@ extern crate log;
 mod auth;
 mod controllers;
 mod judge_actor;
+mod metrics;
 mod models;
 mod schema;
 mod services;
@@ -17,8 +18,10 @@ mod statics;
 
 use actix_cors::Cors;
 use actix_identity::{CookieIdentityPolicy, IdentityService};
+use actix_web::dev::Service;
 use actix_web::middleware::Logger;
 use actix_web::{App, HttpResponse, HttpServer};
+use std::time::Instant;
 
 #[actix_web::get("/")]
 async fn hello() -> impl actix_web::Responder {
@@ -47,6 +50,7 @@ async fn main() -> std::io::Result<()> {
     let auth_duration = time::Duration::hours(i64::from(opt.auth_duration_in_hour));
 
     let judge_actor_addr = judge_actor::start_judge_actor(opt.clone(), pool.clone());
+    let metrics_token = opt.metrics_token.clone();
 
     HttpServer::new(move || {
         App::new()
@@ -54,7 +58,25 @@ async fn main() -> std::io::Result<()> {
             .data(judge_actor::JudgeActorAddr {
                 addr: judge_actor_addr.clone(),
             })
+            .data(controllers::metrics::handler::MetricsToken(
+                metrics_token.clone(),
+            ))
             .wrap(Logger::default())
+            .wrap_fn(|req, srv| {
+                let start = Instant::now();
+                let method = req.method().to_string();
+                let fut = srv.call(req);
+                async move {
+                    let res = fut.await?;
+                    // patterns rather than paths, so ids do not make new series
+                    let route = res
+                        .request()
+                        .match_pattern()
+                        .unwrap_or_else(|| "unmatched".to_owned());
+                    metrics::observe_http(&method, &route, res.status().as_u16(), start.elapsed());
+                    Ok(res)
+                }
+            })
             .wrap(Cors::permissive())
             .wrap(IdentityService::new(
                 CookieIdentityPolicy::new(cookie_secret_key.as_bytes())
@@ -71,11 +93,15 @@ async fn main() -> std::io::Result<()> {
             .configure(controllers::users::route)
             .configure(controllers::problems::route)
             .configure(controllers::judge_servers::route)
+            .configure(controllers::languages::route)
             .configure(controllers::submissions::route)
             .configure(controllers::samples::route)
             .configure(controllers::regions::route)
             .configure(controllers::problem_sets::route)
             .configure(controllers::contests::route)
+            .configure(controllers::rejudges::route)
+            .configure(controllers::hacks::route)
+            .configure(controllers::metrics::route)
     })
     .bind(("0.0.0.0", opt.port))
     .unwrap()
@@ -0,0 +1,145 @@
+use crate::models::submissions::{Submission, COMPILE_ERROR, COMPILE_ERROR_ERR};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use std::time::Duration;
+
+// judging takes from milliseconds on samples to minutes on big problems
+const JUDGE_BUCKETS: [f64; 12] = [
+    0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0,
+];
+
+lazy_static! {
+    pub static ref REGISTRY: Registry = Registry::new();
+    pub static ref JUDGE_DURATION: HistogramVec = register(
+        HistogramVec::new(
+            HistogramOpts::new(
+                "shupdtp_judge_duration_seconds",
+                "Round trip of judge requests by judge server"
+            )
+            .buckets(JUDGE_BUCKETS.to_vec()),
+            &["server"],
+        )
+        .unwrap()
+    );
+    pub static ref VERDICTS: IntCounterVec = register(
+        IntCounterVec::new(
+            Opts::new("shupdtp_verdicts_total", "Finished submissions by verdict"),
+            &["verdict"],
+        )
+        .unwrap()
+    );
+    pub static ref HTTP_DURATION: HistogramVec = register(
+        HistogramVec::new(
+            HistogramOpts::new(
+                "shupdtp_http_request_duration_seconds",
+                "Latency of HTTP requests by route"
+            ),
+            &["method", "route", "status"],
+        )
+        .unwrap()
+    );
+    // the following are filled on every scrape
+    pub static ref QUEUE_DEPTH: IntGaugeVec = register(
+        IntGaugeVec::new(
+            Opts::new("shupdtp_judge_queue_depth", "Judge tasks by lane and whether they are claimed"),
+            &["lane", "state"],
+        )
+        .unwrap()
+    );
+    pub static ref SUBMISSION_STATES: IntGaugeVec = register(
+        IntGaugeVec::new(
+            Opts::new("shupdtp_submissions", "Submissions by state"),
+            &["state"],
+        )
+        .unwrap()
+    );
+    pub static ref SERVER_TASK_NUMBER: IntGaugeVec = register(
+        IntGaugeVec::new(
+            Opts::new("shupdtp_judge_server_task_number", "Tasks running on a judge server"),
+            &["server", "state"],
+        )
+        .unwrap()
+    );
+    pub static ref SERVER_QUARANTINED: IntGaugeVec = register(
+        IntGaugeVec::new(
+            Opts::new(
+                "shupdtp_judge_server_quarantined",
+                "Whether a judge server is quarantined for failing too often"
+            ),
+            &["server"],
+        )
+        .unwrap()
+    );
+    pub static ref SERVER_HEARTBEAT_AGE: IntGaugeVec = register(
+        IntGaugeVec::new(
+            Opts::new(
+                "shupdtp_judge_server_heartbeat_age_seconds",
+                "Seconds since the last heartbeat of a judge server"
+            ),
+            &["server"],
+        )
+        .unwrap()
+    );
+    pub static ref DB_POOL_CONNECTIONS: IntGauge = register(
+        IntGauge::new("shupdtp_db_pool_connections", "Connections opened by the database pool")
+            .unwrap()
+    );
+    pub static ref DB_POOL_IDLE_CONNECTIONS: IntGauge = register(
+        IntGauge::new("shupdtp_db_pool_idle_connections", "Idle connections of the database pool")
+            .unwrap()
+    );
+    pub static ref DB_POOL_MAX_SIZE: IntGauge = register(
+        IntGauge::new("shupdtp_db_pool_max_size", "Most connections the database pool may open")
+            .unwrap()
+    );
+}
+
+fn register<T: prometheus::core::Collector + Clone + 'static>(collector: T) -> T {
+    REGISTRY.register(Box::new(collector.clone())).unwrap();
+    collector
+}
+
+pub fn observe_judge(server: &str, duration: Duration) {
+    JUDGE_DURATION
+        .with_label_values(&[server])
+        .observe(duration.as_secs_f64());
+}
+
+// The compile error, the system error or the first failed result of a finished submission.
+pub fn count_verdict(submission: &Submission) {
+    let verdict = match (submission.err.as_deref(), submission.is_accepted) {
+        (Some(COMPILE_ERROR_ERR), _) => COMPILE_ERROR.to_owned(),
+        // errors come with free text, one label each would grow without bound
+        (Some(_), _) => "SYSTEM_ERROR".to_owned(),
+        (None, Some(true)) => "ACCEPTED".to_owned(),
+        (None, _) => submission
+            .out_results
+            .as_ref()
+            .and_then(|out_results| {
+                let mut out_results: Vec<&String> = out_results
+                    .iter()
+                    .filter(|out_result| *out_result != "SUCCESS")
+                    .collect();
+                out_results.sort();
+                out_results.first().map(|out_result| out_result.to_string())
+            })
+            .unwrap_or_else(|| "UNKNOWN".to_owned()),
+    };
+    VERDICTS.with_label_values(&[&verdict]).inc();
+}
+
+pub fn observe_http(method: &str, route: &str, status: u16, duration: Duration) {
+    HTTP_DURATION
+        .with_label_values(&[method, route, &status.to_string()])
+        .observe(duration.as_secs_f64());
+}
+
+pub fn encode() -> String {
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&REGISTRY.gather(), &mut buffer)
+        .unwrap();
+    String::from_utf8(buffer).unwrap()
+}
@@ -87,6 +87,12 @@ pub struct ContestSettings {
     pub view_after_end: bool,
     pub public_after_end: bool,
     pub submit_after_end: bool,
+    // count compile errors as tries in ACM rank, ICPC does not
+    #[serde(default)]
+    pub compile_error_penalty: bool,
+    // registered users may challenge accepted submissions of others once it starts
+    #[serde(default)]
+    pub allow_hacks: bool,
 }
 
 impl Default for ContestSettings {
@@ -98,6 +104,8 @@ impl Default for ContestSettings {
             view_after_end: true,
             public_after_end: false,
             submit_after_end: true,
+            compile_error_penalty: false,
+            allow_hacks: false,
         }
     }
 }
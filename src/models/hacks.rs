@@ -0,0 +1,64 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+// Every state but Waiting is final.
+pub const WAITING: &str = "Waiting";
+// the target failed on the input, so its verdict is changed
+pub const SUCCESSFUL: &str = "Successful";
+pub const UNSUCCESSFUL: &str = "Unsuccessful";
+// rejected by the validator, or the reference solution failed on it
+pub const INVALID_INPUT: &str = "InvalidInput";
+// judging itself went wrong, the target is left as it was
+pub const FAILED: &str = "Failed";
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
+#[table_name = "hacks"]
+pub struct Hack {
+    pub id: Uuid,
+    pub submission_id: Uuid,
+    pub region: String,
+    pub problem_id: i32,
+    pub hacker_id: i32,
+    pub defender_id: i32,
+    pub input: String,
+    // output of the reference solution
+    pub answer: Option<String>,
+    pub state: String,
+    pub reason: Option<String>,
+    pub create_time: NaiveDateTime,
+    pub finish_time: Option<NaiveDateTime>,
+    // set once a successful hack is appended to the test data
+    pub test_case_id: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlimHack {
+    pub id: Uuid,
+    pub submission_id: Uuid,
+    pub region: String,
+    pub problem_id: i32,
+    pub hacker_id: i32,
+    pub defender_id: i32,
+    pub state: String,
+    pub create_time: NaiveDateTime,
+    pub finish_time: Option<NaiveDateTime>,
+    pub test_case_id: Option<i32>,
+}
+
+impl From<Hack> for SlimHack {
+    fn from(hack: Hack) -> Self {
+        Self {
+            id: hack.id,
+            submission_id: hack.submission_id,
+            region: hack.region,
+            problem_id: hack.problem_id,
+            hacker_id: hack.hacker_id,
+            defender_id: hack.defender_id,
+            state: hack.state,
+            create_time: hack.create_time,
+            finish_time: hack.finish_time,
+            test_case_id: hack.test_case_id,
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+// Lower lanes are served first.
+pub const CONTEST_LANE: i32 = 0;
+pub const PRACTICE_LANE: i32 = 1;
+pub const SAMPLE_LANE: i32 = 2;
+
+pub const LANES: [(i32, &str); 3] = [
+    (CONTEST_LANE, "Contest"),
+    (PRACTICE_LANE, "Practice"),
+    (SAMPLE_LANE, "Sample"),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
+#[table_name = "judge_queue"]
+pub struct JudgeQueueItem {
+    pub submission_id: Uuid,
+    pub enqueue_time: NaiveDateTime,
+    pub lease_time: Option<NaiveDateTime>,
+    pub lane: i32,
+    pub attempts: i32,
+    pub retry_time: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueDepth {
+    pub lane: String,
+    pub waiting: i64,
+    pub judging: i64,
+}
@@ -1,4 +1,92 @@
+use crate::schema::*;
+use std::collections::{HashMap, VecDeque};
 use std::time::SystemTime;
+use uuid::Uuid;
+
+// Admin controlled states of a judge server,
+// only "Active" servers are given new tasks.
+// "Draining" servers finish their in-flight tasks,
+// "Disabled" servers are also not woken up by their heartbeats.
+pub const ACTIVE: &str = "Active";
+pub const DRAINING: &str = "Draining";
+pub const DISABLED: &str = "Disabled";
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
+#[table_name = "judge_servers"]
+pub struct JudgeServerRecord {
+    pub service_url: String,
+    pub state: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HeartbeatBody {
+    pub judger_version: String,
+    pub hostname: String,
+    pub cpu_core: i32,
+    pub memory: f32,
+    pub cpu: f32,
+    pub service_url: Option<String>,
+    // versions of the compilers installed, by language id
+    #[serde(default)]
+    pub compiler_versions: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JudgeServerToken {
+    pub token: String,
+    #[serde(default)]
+    pub require_signature: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JudgeServerTokenConfig {
+    #[serde(default)]
+    pub tokens: Vec<JudgeServerToken>,
+}
+
+// Outcome of one judge request sent to a server.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JudgeOutcome {
+    Success,
+    // the server answered, but with a SYSTEM_ERROR of its own
+    SystemError,
+    Timeout,
+    // refused connections, HTTP errors and malformed answers
+    Failed,
+}
+
+// Rolling error statistics of a judge server, a quarantined server is given no task
+// until its cool-down is over and it passed the probe submissions.
+#[derive(Debug, Clone, Default)]
+pub struct JudgeServerHealth {
+    // latest outcomes first, with the submission judged if any
+    pub recent_outcomes: VecDeque<(JudgeOutcome, Option<Uuid>)>,
+    pub consecutive_failures: i32,
+    pub quarantined_until: Option<SystemTime>,
+    // quarantines in a row without passing the probes, the cool-down doubles with each
+    pub quarantine_count: u32,
+    pub is_probing: bool,
+}
+
+impl JudgeServerHealth {
+    pub fn is_quarantined(&self) -> bool {
+        self.quarantined_until.is_some()
+    }
+
+    pub fn count(&self, outcome: JudgeOutcome) -> usize {
+        self.recent_outcomes
+            .iter()
+            .filter(|(recent_outcome, _)| *recent_outcome == outcome)
+            .count()
+    }
+
+    pub fn system_error_ratio(&self) -> f32 {
+        if self.recent_outcomes.is_empty() {
+            return 0.0;
+        }
+        self.count(JudgeOutcome::SystemError) as f32 / self.recent_outcomes.len() as f32
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct JudgeServerInfo {
@@ -12,6 +100,10 @@ pub struct JudgeServerInfo {
     pub token: String,
     pub heartbeat_time: SystemTime,
     pub is_deprecated: bool,
+    pub state: String,
+    pub health: JudgeServerHealth,
+    // compiler versions reported in heartbeats, by language id
+    pub compiler_versions: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,10 +115,15 @@ pub struct OutJudgeServerInfo {
     pub cpu: f32,
     pub task_number: i32,
     pub service_url: String,
-    pub token: String,
     pub heartbeat_time: SystemTime,
     pub last_heartbeat: i32,
     pub is_deprecated: bool,
+    pub state: String,
+    pub consecutive_failures: i32,
+    pub system_error_ratio: f32,
+    pub timeouts: i32,
+    pub quarantined_until: Option<SystemTime>,
+    pub compiler_versions: HashMap<String, String>,
 }
 
 impl From<JudgeServerInfo> for OutJudgeServerInfo {
@@ -39,10 +136,15 @@ impl From<JudgeServerInfo> for OutJudgeServerInfo {
             cpu: raw.cpu,
             task_number: raw.task_number,
             service_url: raw.service_url,
-            token: raw.token,
             heartbeat_time: raw.heartbeat_time,
             last_heartbeat: raw.heartbeat_time.elapsed().unwrap().as_secs() as i32,
             is_deprecated: raw.is_deprecated,
+            state: raw.state,
+            consecutive_failures: raw.health.consecutive_failures,
+            system_error_ratio: raw.health.system_error_ratio(),
+            timeouts: raw.health.count(JudgeOutcome::Timeout) as i32,
+            quarantined_until: raw.health.quarantined_until,
+            compiler_versions: raw.compiler_versions,
         }
     }
 }
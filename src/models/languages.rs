@@ -1,3 +1,7 @@
+use crate::statics::LANGUAGES;
+use server_core::errors::{ServiceError, ServiceResult};
+use std::collections::HashSet;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompileConfig {
     pub src_name: String,
@@ -11,8 +15,11 @@ pub struct CompileConfig {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RunConfig {
     pub command: String,
+    #[serde(default)]
     pub seccomp_rule: Option<String>,
+    #[serde(default)]
     pub env: Vec<String>,
+    #[serde(default)]
     pub memory_limit_check_only: i32,
 }
 
@@ -39,139 +46,311 @@ pub struct SpjCompileConfig {
     pub compile_command: String,
 }
 
-fn default_env() -> Vec<String> {
-    vec![
-        "LANG=en_US.UTF-8".to_owned(),
-        "LANGUAGE=en_US:en".to_owned(),
-        "LC_ALL=en_US.UTF-8".to_owned(),
-    ]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InteractorConfig {
+    pub exe_name: String,
+    pub command: String,
+    pub seccomp_rule: String,
 }
 
-fn c_lang_config() -> LanguageConfig {
-    LanguageConfig {
-        compile: CompileConfig {
-            src_name: "main.c".to_owned(),
-            exe_name: "main".to_owned(),
-            max_cpu_time: 3000,
-            max_real_time: 5000,
-            max_memory: 128 * 1024 * 1024,
-            compile_command: "/usr/bin/gcc -DONLINE_JUDGE -O2 -w -fmax-errors=3 -std=c99 {src_path} -lm -o {exe_path}".to_owned(),
-        },
-        run: RunConfig {
-            command: "{exe_path}".to_owned(),
-            seccomp_rule: Some("c_cpp".to_owned()),
-            env: default_env(),
-            memory_limit_check_only: 0,
-        }
-    }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InteractorCompileConfig {
+    pub src_name: String,
+    pub exe_name: String,
+    pub max_cpu_time: i32,
+    pub max_real_time: i32,
+    pub max_memory: i32,
+    pub compile_command: String,
 }
 
-pub fn spj_compile_config() -> SpjCompileConfig {
-    SpjCompileConfig {
-        src_name: "spj-{spj_version}.cpp".to_owned(),
-        exe_name: "spj-{spj_version}".to_owned(),
-        max_cpu_time: 3000,
-        max_real_time: 5000,
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckerConfig {
+    pub language: String,
+    pub command: String,
+}
+
+// Checker sources looked for in TestCases on import, with the language they are written in.
+pub const CHECKER_SOURCES: [(&str, &str); 3] = [
+    ("checker.c", "c"),
+    ("checker.cpp", "cpp"),
+    ("checker.py", "py3"),
+];
+
+// Optional reference solution and input validator for hacking, looked for the same way.
+pub const STD_SOURCES: [(&str, &str); 3] = [("std.c", "c"), ("std.cpp", "cpp"), ("std.py", "py3")];
+pub const VALIDATOR_SOURCES: [(&str, &str); 3] = [
+    ("validator.c", "c"),
+    ("validator.cpp", "cpp"),
+    ("validator.py", "py3"),
+];
+
+// testlib.h shipped along with the interactor is found in the test case directory
+pub fn interactor_compile_config() -> InteractorCompileConfig {
+    InteractorCompileConfig {
+        src_name: "interactor-{spj_version}.cpp".to_owned(),
+        exe_name: "interactor-{spj_version}".to_owned(),
+        max_cpu_time: 10000,
+        max_real_time: 20000,
         max_memory: 1024 * 1024 * 1024,
-        compile_command: "/usr/bin/g++ -DONLINE_JUDGE -O2 -w -fmax-errors=3 -std=c99 {src_path} -lm -L /test_case/include -o {exe_path}".to_owned(),
+        compile_command: "/usr/bin/g++ -DONLINE_JUDGE -O2 -w -fmax-errors=3 -std=c++11 -I {test_case_dir} {src_path} -lm -o {exe_path}".to_owned(),
     }
 }
 
-pub fn spj_config() -> SpjConfig {
-    SpjConfig {
-        exe_name: "spj-{spj_version}".to_owned(),
-        command: "{exe_path} {in_file_path} {user_out_file_path}".to_owned(),
+// The interactor talks to the program over its stdin and stdout,
+// the verdict is its exit code and `user_out_file_path` is kept as the output.
+pub fn interactor_config() -> InteractorConfig {
+    InteractorConfig {
+        exe_name: "interactor-{spj_version}".to_owned(),
+        command: "{exe_path} {in_file_path} {user_out_file_path} {answer_file_path}".to_owned(),
         seccomp_rule: "c_cpp".to_owned(),
     }
 }
 
-fn cpp_lang_config() -> LanguageConfig {
-    LanguageConfig {
-        compile: CompileConfig {
-            src_name: "main.cpp".to_owned(),
-            exe_name: "main".to_owned(),
-            max_cpu_time: 3000,
-            max_real_time: 5000,
-            max_memory: 128 * 1024 * 1024,
-            compile_command: "/usr/bin/g++ -DONLINE_JUDGE -O2 -w -fmax-errors=3 -std=c++11 {src_path} -lm -o {exe_path}".to_owned(),
+// Checkers are compiled once into the test case directory, which is `{exe_dir}` here,
+// testlib.h shipped along with the checker is found there too.
+pub fn checker_compile_command(language: &str) -> String {
+    match language {
+        "py3" => "/usr/bin/python3 -m py_compile {src_path}".to_owned(),
+        "c" => "/usr/bin/gcc -DONLINE_JUDGE -O2 -w -fmax-errors=3 -std=c99 -I {exe_dir} {src_path} -lm -o {exe_path}".to_owned(),
+        _ => "/usr/bin/g++ -DONLINE_JUDGE -O2 -w -fmax-errors=3 -std=c++11 -I {exe_dir} {src_path} -lm -o {exe_path}".to_owned(),
+    }
+}
+
+// Same arguments as testlib checkers, the verdict is the exit code.
+pub fn checker_config(language: &str) -> CheckerConfig {
+    CheckerConfig {
+        language: language.to_owned(),
+        command: match language {
+            "py3" => "/usr/bin/python3 {exe_dir}/checker.py {in_file_path} {user_out_file_path} {answer_file_path}".to_owned(),
+            _ => "{exe_dir}/checker {in_file_path} {user_out_file_path} {answer_file_path}".to_owned(),
         },
-        run: RunConfig {
-            command: "{exe_path}".to_owned(),
-            seccomp_rule: Some("c_cpp".to_owned()),
-            env: default_env(),
-            memory_limit_check_only: 0,
+    }
+}
+
+// Validators read the input from stdin like testlib ones, and exit with 0 when it is valid.
+pub fn validator_command(language: &str) -> String {
+    match language {
+        "py3" => "/usr/bin/python3 {exe_dir}/validator.py".to_owned(),
+        _ => "{exe_dir}/validator".to_owned(),
+    }
+}
+
+// Time and memory limits of a language, as multipliers of the base limits of a problem
+// or as absolute limits, which win over multipliers.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LanguageLimits {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_multiplier: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_multiplier: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cpu_time: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_memory: Option<i32>,
+}
+
+impl LanguageLimits {
+    pub fn check(&self) -> Result<(), String> {
+        let multipliers = [self.time_multiplier, self.memory_multiplier];
+        if multipliers
+            .iter()
+            .flatten()
+            .any(|multiplier| multiplier.is_nan() || *multiplier <= 0.0)
+        {
+            return Err("Multipliers must be positive.".to_owned());
+        }
+        if [self.max_cpu_time, self.max_memory]
+            .iter()
+            .flatten()
+            .any(|limit| *limit <= 0)
+        {
+            return Err("Limits must be positive.".to_owned());
         }
+        Ok(())
     }
 }
 
-fn java_lang_config() -> LanguageConfig {
-    LanguageConfig {
-        compile: CompileConfig {
-            src_name: "Main.java".to_owned(),
-            exe_name: "Main".to_owned(),
-            max_cpu_time: 5000,
-            max_real_time: 10000,
-            max_memory: -1,
-            compile_command: "/usr/bin/javac {src_path} -d {exe_dir} -encoding UTF8".to_owned(),
-        },
-        run: RunConfig {
-            command: "/usr/bin/java -cp {exe_dir} -XX:MaxRAM={max_memory}k -Djava.security.manager -Dfile.encoding=UTF-8 -Djava.security.policy==/etc/java_policy -Djava.awt.headless=true Main".to_owned(),
-            seccomp_rule: None,
-            env: default_env(),
-            memory_limit_check_only: 1,
+fn limit(base: i32, absolute: Option<i32>, multiplier: Option<f32>) -> Option<i32> {
+    absolute.or_else(|| multiplier.map(|multiplier| (base as f64 * multiplier as f64) as i32))
+}
+
+// Limits a submission runs with, the limits a problem sets for its language win over
+// the global ones of languages.toml, which win over the base limits.
+pub fn resolve_limits(
+    max_cpu_time: i32,
+    max_memory: i32,
+    global: &LanguageLimits,
+    problem: Option<&LanguageLimits>,
+) -> (i32, i32) {
+    let problem = problem.cloned().unwrap_or_default();
+    let max_cpu_time = limit(max_cpu_time, problem.max_cpu_time, problem.time_multiplier)
+        .or_else(|| limit(max_cpu_time, global.max_cpu_time, global.time_multiplier))
+        .unwrap_or(max_cpu_time);
+    let max_memory = limit(max_memory, problem.max_memory, problem.memory_multiplier)
+        .or_else(|| limit(max_memory, global.max_memory, global.memory_multiplier))
+        .unwrap_or(max_memory);
+    (max_cpu_time, max_memory)
+}
+
+// A language submissions may be written in, as defined in languages.toml.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Language {
+    pub id: String,
+    pub name: String,
+    // disabled languages are kept for old submissions, but no longer accepted
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub limits: LanguageLimits,
+    pub compile: CompileConfig,
+    pub run: RunConfig,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+// What clients are told about a language, with the compiler versions of the judge servers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutLanguage {
+    pub id: String,
+    pub name: String,
+    pub compile_command: String,
+    pub run_command: String,
+    pub compiler_versions: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LanguageRegistry {
+    #[serde(default)]
+    pub languages: Vec<Language>,
+}
+
+impl LanguageRegistry {
+    pub fn get(&self, id: &str) -> Option<&Language> {
+        self.languages.iter().find(|language| language.id == id)
+    }
+}
+
+pub const LANGUAGES_PATH: &str = "languages.toml";
+
+// Parse and check a registry, so a broken file can not replace a working one.
+pub fn parse_languages(content: &str) -> Result<LanguageRegistry, String> {
+    let registry: LanguageRegistry = toml::from_str(content).map_err(|e| e.to_string())?;
+
+    let mut ids = HashSet::new();
+    for language in registry.languages.iter() {
+        if language.id.is_empty() || !ids.insert(language.id.clone()) {
+            return Err(format!(
+                "Empty or duplicated language id \"{}\".",
+                language.id
+            ));
+        }
+        language
+            .limits
+            .check()
+            .map_err(|e| format!("Limits of {}: {}", language.id, e))?;
+        if language.compile.compile_command.trim().is_empty()
+            || language.run.command.trim().is_empty()
+        {
+            return Err(format!("Commands of {} must not be empty.", language.id));
         }
     }
+
+    Ok(registry)
 }
 
-fn py2_lang_config() -> LanguageConfig {
-    LanguageConfig {
-        compile: CompileConfig {
-            src_name: "solution.py".to_owned(),
-            exe_name: "solution.pyc".to_owned(),
-            max_cpu_time: 3000,
-            max_real_time: 5000,
-            max_memory: 128 * 1024 * 1024,
-            compile_command: "/usr/bin/python -m py_compile {src_path}".to_owned(),
-        },
-        run: RunConfig {
-            command: "/usr/bin/python {exe_path}".to_owned(),
-            seccomp_rule: Some("general".to_owned()),
-            env: default_env(),
-            memory_limit_check_only: 0,
-        },
+pub fn load_languages() -> Result<LanguageRegistry, String> {
+    let content = std::fs::read_to_string(LANGUAGES_PATH).map_err(|e| e.to_string())?;
+    parse_languages(&content)
+}
+
+// Disabled languages are known too, settings naming them stay valid.
+pub fn check_known_language(id: &str) -> ServiceResult<()> {
+    if LANGUAGES.read().unwrap().get(id).is_none() {
+        let hint = format!("Unknown language {}.", id);
+        return Err(ServiceError::BadRequest(hint));
     }
+    Ok(())
 }
 
-fn py3_lang_config() -> LanguageConfig {
-    LanguageConfig {
-        compile: CompileConfig {
-            src_name: "solution.py".to_owned(),
-            exe_name: "__pycache__/solution.cpython-36.pyc".to_owned(),
-            max_cpu_time: 3000,
-            max_real_time: 5000,
-            max_memory: 128 * 1024 * 1024,
-            compile_command: "/usr/bin/python3 -m py_compile {src_path}".to_owned(),
-        },
-        run: RunConfig {
-            command: "/usr/bin/python3 {exe_path}".to_owned(),
-            seccomp_rule: Some("general".to_owned()),
-            env: {
-                let mut default_env = default_env();
-                default_env.push("PYTHONIOENCODING=UTF-8".to_owned());
-                default_env
-            },
-            memory_limit_check_only: 0,
-        },
+pub fn get_language(id: &str) -> ServiceResult<Language> {
+    match LANGUAGES.read().unwrap().get(id) {
+        Some(language) if language.enabled => Ok(language.clone()),
+        Some(_) => {
+            let hint = format!("Language {} is disabled.", id);
+            Err(ServiceError::BadRequest(hint))
+        }
+        None => {
+            let hint = format!("Unknown language {}.", id);
+            Err(ServiceError::BadRequest(hint))
+        }
     }
 }
 
-pub fn get_lang_config(language: &str) -> LanguageConfig {
-    match language {
-        "c" => c_lang_config(),
-        "cpp" => cpp_lang_config(),
-        "java" => java_lang_config(),
-        "py2" => py2_lang_config(),
-        "py3" => py3_lang_config(),
-        _ => c_lang_config(),
+pub fn get_lang_config(id: &str) -> ServiceResult<LanguageConfig> {
+    get_language(id).map(|language| LanguageConfig {
+        compile: language.compile,
+        run: language.run,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn languages_come_from_the_registry() {
+        let registry = parse_languages(&std::fs::read_to_string(LANGUAGES_PATH).unwrap()).unwrap();
+        for id in ["c", "cpp17", "java", "py3", "pypy3", "go", "rust", "kotlin"].iter() {
+            assert!(registry.get(id).is_some(), "{} is missing", id);
+        }
+
+        let duplicated = r#"
+            [[languages]]
+            id = "c"
+            name = "C"
+            [languages.compile]
+            src_name = "main.c"
+            exe_name = "main"
+            max_cpu_time = 3000
+            max_real_time = 5000
+            max_memory = 134217728
+            compile_command = "/usr/bin/gcc {src_path} -o {exe_path}"
+            [languages.run]
+            command = "{exe_path}"
+        "#
+        .repeat(2);
+        assert!(parse_languages(&duplicated).is_err());
+
+        match get_lang_config("brainfuck") {
+            Err(ServiceError::BadRequest(_)) => (),
+            res => panic!("expected BadRequest, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn limits_of_problems_win_over_those_of_languages() {
+        let global = LanguageLimits {
+            time_multiplier: Some(2.0),
+            memory_multiplier: Some(1.5),
+            ..Default::default()
+        };
+        assert_eq!(resolve_limits(1000, 1024, &global, None), (2000, 1536));
+
+        let problem = LanguageLimits {
+            time_multiplier: Some(3.0),
+            max_memory: Some(4096),
+            memory_multiplier: Some(8.0),
+            ..Default::default()
+        };
+        assert_eq!(
+            resolve_limits(1000, 1024, &global, Some(&problem)),
+            (3000, 4096)
+        );
+        assert_eq!(
+            resolve_limits(1000, 1024, &LanguageLimits::default(), None),
+            (1000, 1024)
+        );
     }
 }
@@ -0,0 +1,9 @@
+use diesel::sql_types::{BigInt, Text};
+
+#[derive(Debug, Clone, QueryableByName)]
+pub struct SubmissionStateCount {
+    #[sql_type = "Text"]
+    pub state: String,
+    #[sql_type = "BigInt"]
+    pub count: i64,
+}
@@ -1,13 +1,17 @@
 pub mod access_control_list;
 pub mod contests;
+pub mod hacks;
+pub mod judge_queue;
 pub mod judge_servers;
 pub mod languages;
+pub mod metrics;
 pub mod problem_sets;
 pub mod problems;
 pub mod ranks;
 pub mod region_access_settings;
 pub mod region_links;
 pub mod regions;
+pub mod rejudges;
 pub mod samples;
 pub mod statistics;
 pub mod submissions;
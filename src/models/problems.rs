@@ -1,4 +1,14 @@
+use super::languages::LanguageLimits;
 use crate::schema::*;
+use std::collections::HashMap;
+
+// Test data of a problem is imported into a directory named by its id under this one,
+// every judge backend and helper reads it from there.
+pub const TEST_CASE_ROOT: &str = "data/test_cases";
+
+pub fn test_case_dir(problem_id: i32) -> String {
+    format!("{}/{}", TEST_CASE_ROOT, problem_id)
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, Queryable)]
 pub struct RawProblem {
@@ -45,12 +55,117 @@ pub struct ProblemContents {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProblemSettings {
     pub is_spj: bool,
-    pub high_performance_max_cpu_time: i32,
-    pub high_performance_max_memory: i32,
-    pub other_max_cpu_time: i32,
-    pub other_max_memory: i32,
+    #[serde(default)]
+    pub is_interactive: bool,
+    // base limits, problems imported before per-language limits call them high performance limits
+    #[serde(alias = "high_performance_max_cpu_time")]
+    pub max_cpu_time: i32,
+    #[serde(alias = "high_performance_max_memory")]
+    pub max_memory: i32,
+    // limits of languages by id, see languages::resolve_limits
+    #[serde(default)]
+    pub language_limits: HashMap<String, LanguageLimits>,
+    // limits of the languages which were not high performance, turned into `language_limits`
+    #[serde(default, skip_serializing)]
+    pub other_max_cpu_time: Option<i32>,
+    #[serde(default, skip_serializing)]
+    pub other_max_memory: Option<i32>,
     pub opaque_output: bool,
     pub test_case_count: Option<i32>,
+    // language of the checker found on import
+    #[serde(default)]
+    pub checker_language: Option<String>,
+    // languages of the reference solution and the input validator found on import,
+    // hacking needs the reference solution
+    #[serde(default)]
+    pub std_language: Option<String>,
+    #[serde(default)]
+    pub validator_language: Option<String>,
+    // digest of the test data on import, results are only reused within the same version
+    #[serde(default)]
+    pub test_data_version: Option<String>,
+    // weight of every test case in order, all test cases weigh 1 when omitted
+    #[serde(default)]
+    pub test_case_scores: Option<Vec<i32>>,
+    #[serde(default)]
+    pub subtasks: Option<Vec<Subtask>>,
+    #[serde(default)]
+    pub comparator: Comparator,
+}
+
+// languages which used to be limited by the other limits instead of the high performance ones
+const LEGACY_OTHER_LANGUAGES: [&str; 4] = ["java", "kotlin", "py2", "py3"];
+
+impl ProblemSettings {
+    // Take what import found in the test data from the stored settings,
+    // editing only changes limits, scores and how outputs are checked.
+    pub fn keep_imported(&mut self, stored: &ProblemSettings) {
+        self.is_spj = stored.is_spj;
+        self.is_interactive = stored.is_interactive;
+        self.test_case_count = stored.test_case_count;
+        self.checker_language = stored.checker_language.clone();
+        self.std_language = stored.std_language.clone();
+        self.validator_language = stored.validator_language.clone();
+        self.test_data_version = stored.test_data_version.clone();
+    }
+
+    // Turn the other limits of settings written before per-language limits into
+    // absolute limits of the languages they applied to.
+    pub fn upgrade_limits(&mut self) {
+        let (max_cpu_time, max_memory) =
+            (self.other_max_cpu_time.take(), self.other_max_memory.take());
+        if max_cpu_time.is_none() && max_memory.is_none() {
+            return;
+        }
+        for language in LEGACY_OTHER_LANGUAGES.iter() {
+            let limits = self
+                .language_limits
+                .entry(language.to_string())
+                .or_default();
+            limits.max_cpu_time = limits.max_cpu_time.or(max_cpu_time);
+            limits.max_memory = limits.max_memory.or(max_memory);
+        }
+    }
+}
+
+// How the output of a program is checked against the answer when there is no special judge.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum Comparator {
+    // whitespace at the end of the whole output is ignored, judge servers compare the md5
+    #[default]
+    Stripped,
+    Exact,
+    TrailingWhitespace,
+    AllWhitespace,
+    CaseInsensitiveTokens,
+    // numbers are equal within either epsilon, other tokens compare exactly
+    Float {
+        #[serde(default)]
+        abs_eps: f64,
+        #[serde(default)]
+        rel_eps: f64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubtaskRule {
+    // all or nothing, the worst test case decides
+    Min,
+    // every accepted test case earns its weighted share
+    Sum,
+}
+
+// Subtasks are numbered from 1 in the order they are declared,
+// `range` is the inclusive range of test case ids and dependencies refer to earlier subtasks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Subtask {
+    pub range: (i32, i32),
+    pub score: i32,
+    pub rule: SubtaskRule,
+    #[serde(default)]
+    pub dependencies: Vec<i32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Queryable)]
@@ -138,3 +253,28 @@ pub struct ProblemForm {
     pub contents: Option<String>,
     pub settings: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_of_languages_come_from_old_settings() {
+        // settings written before per-language limits
+        let mut settings: ProblemSettings = serde_json::from_value(serde_json::json!({
+            "is_spj": false,
+            "high_performance_max_cpu_time": 1000,
+            "high_performance_max_memory": 1024,
+            "other_max_cpu_time": 2000,
+            "other_max_memory": 2048,
+            "opaque_output": false,
+            "test_case_count": 1,
+        }))
+        .unwrap();
+        settings.upgrade_limits();
+        assert_eq!((settings.max_cpu_time, settings.max_memory), (1000, 1024));
+        let py3 = &settings.language_limits["py3"];
+        assert_eq!((py3.max_cpu_time, py3.max_memory), (Some(2000), Some(2048)));
+        assert!(!settings.language_limits.contains_key("cpp"));
+    }
+}
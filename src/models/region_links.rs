@@ -27,6 +27,7 @@ pub struct RawLinkedProblemColumn {
     pub problem_tags: Vec<String>,
     pub problem_difficulty: f64,
     pub is_released: bool,
+    pub score: Option<i32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,16 +38,33 @@ pub struct LinkedProblemColumn {
     pub submit_times: i32,
     pub accept_times: i32,
     pub error_times: i32,
+    pub score: Option<i32>,
+    pub best_score: Option<i32>,
 }
 
 use crate::models::statistics::get_results;
+use diesel::prelude::*;
 use server_core::database::*;
 pub fn get_column_from_raw(
     conn: &PooledConnection,
     raw: RawLinkedProblemColumn,
+    user_id: Option<i32>,
 ) -> ServiceResult<LinkedProblemColumn> {
     let statistic = get_results(conn, raw.region.clone(), raw.problem_id)?;
 
+    let best_score: Option<i32> = match user_id {
+        Some(user_id) => {
+            use crate::schema::submissions as submissions_schema;
+            submissions_schema::table
+                .filter(submissions_schema::region.eq(raw.region.clone()))
+                .filter(submissions_schema::problem_id.eq(raw.problem_id))
+                .filter(submissions_schema::user_id.eq(user_id))
+                .select(diesel::dsl::max(submissions_schema::score))
+                .first(conn)?
+        }
+        None => None,
+    };
+
     Ok(LinkedProblemColumn {
         region: raw.region,
         inner_id: raw.inner_id,
@@ -62,5 +80,7 @@ pub fn get_column_from_raw(
         submit_times: statistic.submit_times,
         accept_times: statistic.accept_times,
         error_times: statistic.error_times,
+        score: raw.score,
+        best_score,
     })
 }
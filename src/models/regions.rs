@@ -8,6 +8,8 @@ pub struct Region {
     pub title: String,
     pub has_access_setting: bool,
     pub introduction: Option<String>,
+    // languages accepted in the region, all enabled ones when None
+    pub allowed_languages: Option<Vec<String>>,
 }
 
 #[derive(AsChangeset)]
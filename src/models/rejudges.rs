@@ -0,0 +1,47 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
+#[table_name = "rejudge_records"]
+pub struct RejudgeRecord {
+    pub batch_id: Uuid,
+    pub submission_id: Uuid,
+    pub create_time: NaiveDateTime,
+    pub old_is_accepted: Option<bool>,
+    pub old_err: Option<String>,
+    pub old_out_results: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RejudgeChange {
+    pub submission_id: Uuid,
+    pub problem_id: i32,
+    pub user_id: i32,
+    pub region: Option<String>,
+    pub old_is_accepted: Option<bool>,
+    pub new_is_accepted: Option<bool>,
+    pub old_err: Option<String>,
+    pub new_err: Option<String>,
+    pub old_out_results: Option<Vec<String>>,
+    pub new_out_results: Option<Vec<String>>,
+}
+
+// A submission left as it was, because its problem or language no longer resolves.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RejudgeSkip {
+    pub submission_id: Uuid,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RejudgeReport {
+    pub batch_id: Uuid,
+    pub create_time: NaiveDateTime,
+    pub total: i32,
+    pub finished: i32,
+    pub changed: Vec<RejudgeChange>,
+    // only known when the rejudge is created, skipped submissions have no record
+    #[serde(default)]
+    pub skipped: Vec<RejudgeSkip>,
+}
@@ -25,6 +25,7 @@ pub struct ResultCount {
     pub memory_limit_exceeded: i32,
     pub runtime_error: i32,
     pub system_error: i32,
+    pub compile_error: i32,
     pub unknown_error: i32,
 }
 
@@ -70,6 +71,7 @@ fn count_results(conn: &PooledConnection, region: &str, problem_id: i32) -> Serv
             memory_limit_exceeded: 0,
             runtime_error: 0,
             system_error: 0,
+            compile_error: 0,
             unknown_error: 0,
         },
     };
@@ -144,6 +146,9 @@ fn update_submission_statistics(statistics: &mut SubmissionStatistics, submissio
                 "SYSTEM_ERROR" => {
                     statistics.result_count.system_error += 1;
                 }
+                "COMPILE_ERROR" => {
+                    statistics.result_count.compile_error += 1;
+                }
                 "UNKNOWN_ERROR" => {
                     statistics.result_count.unknown_error += 1;
                 }
@@ -1,7 +1,8 @@
 use super::languages::*;
+use super::problems::{Comparator, Subtask, SubtaskRule};
 use crate::schema::submissions;
 use chrono::NaiveDateTime;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,6 +23,13 @@ pub struct JudgeSettings {
     pub spj_config: Option<SpjConfig>,
     pub spj_compile_config: Option<SpjCompileConfig>,
     pub spj_src: Option<String>,
+    // replaces the spj fields, the checker is compiled in the test case directory on import
+    pub checker: Option<CheckerConfig>,
+    pub interactor_config: Option<InteractorConfig>,
+    pub interactor_compile_config: Option<InteractorCompileConfig>,
+    pub interactor_src: Option<String>,
+    // None keeps the comparison of judge servers, which is Comparator::Stripped
+    pub comparator: Option<Comparator>,
     pub output: bool,
 }
 
@@ -43,6 +51,9 @@ pub struct RawJudgeResultData {
     pub test_case: String,
     pub output_md5: Option<String>,
     pub output: Option<String>,
+    // judge servers do not send stderr, only the local backend does along with output
+    #[serde(default)]
+    pub stderr: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -57,6 +68,8 @@ pub struct JudgeResultData {
     pub test_case: String,
     pub output_md5: Option<String>,
     pub output: Option<String>,
+    #[serde(default)]
+    pub stderr: Option<String>,
 }
 
 impl From<RawJudgeResultData> for JudgeResultData {
@@ -99,10 +112,57 @@ impl From<RawJudgeResultData> for JudgeResultData {
             test_case: raw.test_case,
             output_md5: raw.output_md5,
             output: raw.output,
+            stderr: raw.stderr,
         }
     }
 }
 
+pub const COMPILE_ERROR: &str = "COMPILE_ERROR";
+// `err` of judge results when the submission does not compile, shown as COMPILE_ERROR
+pub const COMPILE_ERROR_ERR: &str = "CompileError";
+
+// Outcome of running a source on custom input, nothing of it is stored.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunResult {
+    pub err: Option<String>,
+    pub err_reason: Option<String>,
+    pub result: Option<String>,
+    pub stdout: Option<String>,
+    pub stderr: Option<String>,
+    pub cpu_time: Option<i32>,
+    pub real_time: Option<i32>,
+    pub memory: Option<i32>,
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+}
+
+impl From<JudgeResult> for RunResult {
+    fn from(judge_result: JudgeResult) -> Self {
+        let detail = judge_result
+            .details
+            .and_then(|details| details.into_iter().next());
+        Self {
+            err: judge_result.err,
+            err_reason: judge_result.err_reason,
+            // there is no answer to compare with, so a wrong answer only means the program exited normally
+            result: detail.as_ref().map(|detail| match detail.result.as_str() {
+                "WRONG_ANSWER" => "SUCCESS".to_owned(),
+                result => result.to_owned(),
+            }),
+            stdout: detail.as_ref().and_then(|detail| detail.output.clone()),
+            stderr: detail.as_ref().and_then(|detail| detail.stderr.clone()),
+            cpu_time: detail.as_ref().map(|detail| detail.cpu_time),
+            real_time: detail.as_ref().map(|detail| detail.real_time),
+            memory: detail.as_ref().map(|detail| detail.memory),
+            exit_code: detail.as_ref().map(|detail| detail.exit_code),
+            signal: detail.as_ref().map(|detail| detail.signal),
+        }
+    }
+}
+
+// compiler output is kept in err_reason, long template errors get cut
+const MAX_COMPILE_ERROR_LENGTH: usize = 4096;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JudgeResult {
     pub err: Option<String>,
@@ -111,6 +171,18 @@ pub struct JudgeResult {
     pub max_time: Option<i32>,
     pub max_memory: Option<i32>,
     pub details: Option<Vec<JudgeResultData>>,
+    #[serde(default)]
+    pub subtasks: Option<Vec<SubtaskResult>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubtaskResult {
+    pub id: i32,
+    pub score: i32,
+    pub full_score: i32,
+    pub is_accepted: bool,
+    // a dependency was not accepted, so the test cases were not counted
+    pub is_skipped: bool,
 }
 
 impl From<RawJudgeResult> for JudgeResult {
@@ -161,25 +233,153 @@ impl From<RawJudgeResult> for JudgeResult {
             None
         };
 
+        let is_compile_error = raw.err.as_deref() == Some(COMPILE_ERROR_ERR);
+
         Self {
             err: raw.err.clone(),
             err_reason: if raw.err.is_some() {
-                Some(serde_json::from_value::<String>(raw.data.clone()).unwrap())
+                let mut err_reason = serde_json::from_value::<String>(raw.data.clone()).unwrap();
+                if is_compile_error && err_reason.len() > MAX_COMPILE_ERROR_LENGTH {
+                    let mut end = MAX_COMPILE_ERROR_LENGTH;
+                    while !err_reason.is_char_boundary(end) {
+                        end -= 1;
+                    }
+                    err_reason.truncate(end);
+                }
+                Some(err_reason)
             } else {
                 None
             },
             is_accepted: if raw.err.is_none() {
                 Some(is_accepted)
+            } else if is_compile_error {
+                Some(false)
             } else {
                 None
             },
             max_time: max_time,
             max_memory: max_memory,
             details: details,
+            subtasks: None,
         }
     }
 }
 
+impl JudgeResult {
+    // Verdicts of all test cases, a compile error is a verdict of its own.
+    pub fn out_results(&self) -> Option<HashSet<String>> {
+        if self.err.as_deref() == Some(COMPILE_ERROR_ERR) {
+            let mut set = HashSet::new();
+            set.insert(COMPILE_ERROR.to_owned());
+            return Some(set);
+        }
+
+        self.details
+            .as_ref()
+            .map(|details| details.iter().map(|detail| detail.result.clone()).collect())
+    }
+
+    // Weighted share of `full_score` earned by the accepted test cases, rounded down.
+    // Test cases missing from `weights` weigh 1, errors before running any test case get 0.
+    pub fn score(&self, weights: &HashMap<String, i32>, full_score: i32) -> i32 {
+        let details = match self.details.as_ref() {
+            Some(details) => details,
+            None => return 0,
+        };
+
+        let mut total_weight = 0;
+        let mut accepted_weight = 0;
+        for detail in details {
+            let weight = *weights.get(&detail.test_case).unwrap_or(&1);
+            total_weight += weight;
+            if detail.result == "SUCCESS" {
+                accepted_weight += weight;
+            }
+        }
+
+        if total_weight <= 0 {
+            return 0;
+        }
+        (full_score as i64 * accepted_weight as i64 / total_weight as i64) as i32
+    }
+
+    // Fold the test case verdicts into subtasks, dependencies are always earlier subtasks.
+    // Test cases the judge server did not report count as failed.
+    pub fn fold_subtasks(
+        &self,
+        subtasks: &[Subtask],
+        weights: &HashMap<String, i32>,
+    ) -> Vec<SubtaskResult> {
+        let accepted: HashSet<&str> = self
+            .details
+            .iter()
+            .flatten()
+            .filter(|detail| detail.result == "SUCCESS")
+            .map(|detail| detail.test_case.as_str())
+            .collect();
+
+        let mut res: Vec<SubtaskResult> = Vec::new();
+        for (index, subtask) in subtasks.iter().enumerate() {
+            let is_skipped = subtask.dependencies.iter().any(|dependency| {
+                !matches!(res.get(*dependency as usize - 1), Some(result) if result.is_accepted)
+            });
+
+            let mut total_weight = 0;
+            let mut accepted_weight = 0;
+            let mut is_accepted = !is_skipped;
+            for test_case in subtask.range.0..=subtask.range.1 {
+                let test_case = test_case.to_string();
+                let weight = *weights.get(&test_case).unwrap_or(&1);
+                total_weight += weight;
+                if accepted.contains(test_case.as_str()) {
+                    accepted_weight += weight;
+                } else {
+                    is_accepted = false;
+                }
+            }
+
+            let score = if is_skipped || total_weight <= 0 {
+                0
+            } else {
+                match subtask.rule {
+                    SubtaskRule::Min if is_accepted => subtask.score,
+                    SubtaskRule::Min => 0,
+                    SubtaskRule::Sum => {
+                        (subtask.score as i64 * accepted_weight as i64 / total_weight as i64) as i32
+                    }
+                }
+            };
+
+            res.push(SubtaskResult {
+                id: index as i32 + 1,
+                score,
+                full_score: subtask.score,
+                is_accepted,
+                is_skipped,
+            });
+        }
+
+        res
+    }
+}
+
+// Subtask scores scaled to `full_score`, rounded down.
+pub fn subtasks_score(subtask_results: &[SubtaskResult], full_score: i32) -> i32 {
+    let total: i64 = subtask_results
+        .iter()
+        .map(|result| result.full_score as i64)
+        .sum();
+    let earned: i64 = subtask_results
+        .iter()
+        .map(|result| result.score as i64)
+        .sum();
+
+    if total <= 0 {
+        return 0;
+    }
+    (full_score as i64 * earned / total) as i32
+}
+
 #[derive(Debug, Clone, Serialize, Queryable)]
 pub struct RawSubmission {
     pub id: Uuid,
@@ -197,6 +397,9 @@ pub struct RawSubmission {
     pub language: Option<String>,
     pub err: Option<String>,
     pub out_results: Option<Vec<String>>,
+    pub score: Option<i32>,
+    pub content_hash: Option<String>,
+    pub is_cached: bool,
 }
 
 #[derive(Debug, Clone, Deserialize, Insertable, Queryable)]
@@ -216,6 +419,9 @@ pub struct InsertableSubmission {
     pub max_memory: Option<i32>,
     pub language: Option<String>,
     pub err: Option<String>,
+    // identical judge settings on the same test data give the same result, see content_hash
+    pub content_hash: Option<String>,
+    pub is_cached: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -235,6 +441,9 @@ pub struct Submission {
     pub language: Option<String>,
     pub err: Option<String>,
     pub out_results: Option<HashSet<String>>,
+    pub score: Option<i32>,
+    // the result was reused from an identical earlier submission
+    pub is_cached: bool,
 }
 
 impl From<RawSubmission> for Submission {
@@ -258,22 +467,13 @@ impl From<RawSubmission> for Submission {
             max_memory: raw.max_memory,
             language: raw.language,
             err: raw.err,
-            out_results: {
-                if let Some(result) = raw.result {
-                    let result = serde_json::from_str::<JudgeResult>(&result).unwrap();
-                    if let Some(details) = result.details {
-                        let mut set = std::collections::HashSet::new();
-                        for detail in details {
-                            set.insert(detail.result);
-                        }
-                        Some(set)
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            },
+            out_results: raw.result.and_then(|result| {
+                serde_json::from_str::<JudgeResult>(&result)
+                    .unwrap()
+                    .out_results()
+            }),
+            score: raw.score,
+            is_cached: raw.is_cached,
         }
     }
 }
@@ -287,10 +487,12 @@ pub struct SlimSubmission {
     pub submit_time: NaiveDateTime,
     pub is_accepted: Option<bool>,
     pub out_results: Option<HashSet<String>>,
+    pub score: Option<i32>,
     pub max_time: Option<i32>,
     pub max_memory: Option<i32>,
     pub language: Option<String>,
     pub err: Option<String>,
+    pub is_cached: bool,
 }
 
 impl From<RawSubmission> for SlimSubmission {
@@ -302,26 +504,119 @@ impl From<RawSubmission> for SlimSubmission {
             state: raw.state,
             submit_time: raw.submit_time,
             is_accepted: raw.is_accepted,
-            out_results: {
-                if let Some(result) = raw.result {
-                    let result = serde_json::from_str::<JudgeResult>(&result).unwrap();
-                    if let Some(details) = result.details {
-                        let mut set = std::collections::HashSet::new();
-                        for detail in details {
-                            set.insert(detail.result);
-                        }
-                        Some(set)
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            },
+            out_results: raw.result.and_then(|result| {
+                serde_json::from_str::<JudgeResult>(&result)
+                    .unwrap()
+                    .out_results()
+            }),
+            score: raw.score,
             max_time: raw.max_time,
             max_memory: raw.max_memory,
             language: raw.language,
             err: raw.err,
+            is_cached: raw.is_cached,
         }
     }
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmissionEvent {
+    pub id: Uuid,
+    pub state: String,
+    pub queue_position: Option<usize>,
+    pub result: Option<JudgeResult>,
+    pub score: Option<i32>,
+    pub err: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A result with one detail per verdict, test cases are numbered from 1.
+    fn result_of(verdicts: &[&str]) -> JudgeResult {
+        let details = verdicts
+            .iter()
+            .enumerate()
+            .map(|(index, verdict)| JudgeResultData {
+                cpu_time: 1,
+                real_time: 1,
+                memory: 0,
+                signal: 0,
+                exit_code: 0,
+                error: "SUCCESS".to_owned(),
+                result: (*verdict).to_owned(),
+                test_case: (index + 1).to_string(),
+                output_md5: None,
+                output: None,
+                stderr: None,
+            })
+            .collect();
+        JudgeResult {
+            err: None,
+            err_reason: None,
+            is_accepted: Some(verdicts.iter().all(|verdict| *verdict == "SUCCESS")),
+            max_time: Some(1),
+            max_memory: Some(0),
+            details: Some(details),
+            subtasks: None,
+        }
+    }
+
+    fn compile_error() -> JudgeResult {
+        JudgeResult {
+            err: Some(COMPILE_ERROR_ERR.to_owned()),
+            err_reason: Some("main.c:1:1: error".to_owned()),
+            is_accepted: Some(false),
+            max_time: None,
+            max_memory: None,
+            details: None,
+            subtasks: None,
+        }
+    }
+
+    #[test]
+    fn score_weighs_test_cases() {
+        let result = result_of(&["SUCCESS", "WRONG_ANSWER"]);
+        assert_eq!(result.score(&HashMap::new(), 100), 50);
+
+        let mut weights = HashMap::new();
+        weights.insert("1".to_owned(), 3);
+        weights.insert("2".to_owned(), 1);
+        assert_eq!(result.score(&weights, 100), 75);
+        assert_eq!(result.score(&weights, 30), 22);
+
+        let result = result_of(&["SUCCESS", "SUCCESS"]);
+        assert_eq!(result.score(&weights, 30), 30);
+
+        assert_eq!(compile_error().score(&weights, 100), 0);
+    }
+
+    #[test]
+    fn subtasks_fold_with_dependencies() {
+        let subtask = |range, score, rule, dependencies| Subtask {
+            range,
+            score,
+            rule,
+            dependencies,
+        };
+        let subtasks = vec![
+            subtask((1, 1), 30, SubtaskRule::Min, vec![]),
+            subtask((2, 2), 30, SubtaskRule::Min, vec![]),
+            subtask((1, 2), 40, SubtaskRule::Sum, vec![1]),
+            subtask((1, 1), 10, SubtaskRule::Min, vec![2]),
+        ];
+
+        let result = result_of(&["SUCCESS", "WRONG_ANSWER"]);
+        let subtask_results = result.fold_subtasks(&subtasks, &HashMap::new());
+        let scores: Vec<i32> = subtask_results.iter().map(|result| result.score).collect();
+        assert_eq!(scores, vec![30, 0, 20, 0]);
+        assert!(subtask_results[0].is_accepted);
+        assert!(!subtask_results[2].is_skipped);
+        assert!(subtask_results[3].is_skipped);
+        assert_eq!(subtasks_score(&subtask_results, 100), 45);
+
+        let subtask_results = compile_error().fold_subtasks(&subtasks, &HashMap::new());
+        assert_eq!(subtasks_score(&subtask_results, 100), 0);
+    }
+}
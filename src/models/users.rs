@@ -128,3 +128,10 @@ pub struct UserSubmissionTime {
     pub date: NaiveDate,
     pub count: i32,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserProblemScore {
+    pub region: String,
+    pub problem_id: i32,
+    pub best_score: i32,
+}
@@ -19,6 +19,42 @@ table! {
     }
 }
 
+table! {
+    hacks (id) {
+        id -> Uuid,
+        submission_id -> Uuid,
+        region -> Text,
+        problem_id -> Int4,
+        hacker_id -> Int4,
+        defender_id -> Int4,
+        input -> Text,
+        answer -> Nullable<Text>,
+        state -> Text,
+        reason -> Nullable<Text>,
+        create_time -> Timestamp,
+        finish_time -> Nullable<Timestamp>,
+        test_case_id -> Nullable<Int4>,
+    }
+}
+
+table! {
+    judge_queue (submission_id) {
+        submission_id -> Uuid,
+        enqueue_time -> Timestamp,
+        lease_time -> Nullable<Timestamp>,
+        lane -> Int4,
+        attempts -> Int4,
+        retry_time -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    judge_servers (service_url) {
+        service_url -> Text,
+        state -> Text,
+    }
+}
+
 table! {
     problem_sets (region) {
         region -> Text,
@@ -63,6 +99,18 @@ table! {
         title -> Text,
         has_access_setting -> Bool,
         introduction -> Nullable<Text>,
+        allowed_languages -> Nullable<Array<Text>>,
+    }
+}
+
+table! {
+    rejudge_records (batch_id, submission_id) {
+        batch_id -> Uuid,
+        submission_id -> Uuid,
+        create_time -> Timestamp,
+        old_is_accepted -> Nullable<Bool>,
+        old_err -> Nullable<Text>,
+        old_out_results -> Nullable<Array<Text>>,
     }
 }
 
@@ -90,6 +138,9 @@ table! {
         language -> Nullable<Text>,
         err -> Nullable<Text>,
         out_results -> Nullable<Array<Text>>,
+        score -> Nullable<Int4>,
+        content_hash -> Nullable<Text>,
+        is_cached -> Bool,
     }
 }
 
@@ -104,14 +155,22 @@ table! {
     }
 }
 
+joinable!(hacks -> submissions (submission_id));
+joinable!(judge_queue -> submissions (submission_id));
+joinable!(rejudge_records -> submissions (submission_id));
+
 allow_tables_to_appear_in_same_query!(
     access_control_list,
     contests,
+    hacks,
+    judge_queue,
+    judge_servers,
     problem_sets,
     problems,
     region_access_settings,
     region_links,
     regions,
+    rejudge_records,
     samples,
     submissions,
     users,
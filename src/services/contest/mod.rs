@@ -41,6 +41,7 @@ pub fn create(
             title: title.clone(),
             has_access_setting: true,
             introduction: introduction.clone(),
+            allowed_languages: None,
         })
         .execute(conn)?;
 
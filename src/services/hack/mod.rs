@@ -0,0 +1,225 @@
+use crate::auth::region::check_acl;
+use crate::judge_actor::{hack::StartHack, JudgeActorAddr};
+use crate::models::contests::{get_contest_state, Contest, ContestState, RawContest};
+use crate::models::hacks::*;
+use crate::models::problems;
+use crate::models::submissions::RawSubmission;
+use crate::models::utils::SizedList;
+use crate::services::problem::utils::append_test_case;
+use actix_web::web;
+use diesel::prelude::*;
+use server_core::database::{db_connection, Pool};
+use server_core::errors::{ServiceError, ServiceResult};
+use server_core::utils::time::get_cur_naive_date_time;
+use uuid::Uuid;
+
+// hack inputs are kept in the database, so they are kept small
+const MAX_HACK_INPUT_SIZE: usize = 1024 * 1024;
+
+pub fn create(
+    submission_id: Uuid,
+    input: String,
+    user_id: i32,
+    pool: web::Data<Pool>,
+    judge_actor: web::Data<JudgeActorAddr>,
+) -> ServiceResult<Uuid> {
+    if input.len() > MAX_HACK_INPUT_SIZE {
+        let hint = format!("Input is longer than {} bytes.", MAX_HACK_INPUT_SIZE);
+        return Err(ServiceError::BadRequest(hint));
+    }
+
+    let conn = &db_connection(&pool)?;
+
+    use crate::schema::submissions as submissions_schema;
+    let target: RawSubmission = submissions_schema::table
+        .filter(submissions_schema::id.eq(submission_id))
+        .first(conn)?;
+
+    let region = match target.region.clone() {
+        Some(region) => region,
+        None => {
+            let hint = "Only submissions of a contest can be hacked.".to_string();
+            return Err(ServiceError::BadRequest(hint));
+        }
+    };
+
+    use crate::schema::contests as contests_schema;
+    let raw_contest: Option<RawContest> = contests_schema::table
+        .filter(contests_schema::region.eq(region.clone()))
+        .first(conn)
+        .optional()?;
+    let contest = match raw_contest {
+        Some(raw_contest) => Contest::from(raw_contest),
+        None => {
+            let hint = "Only submissions of a contest can be hacked.".to_string();
+            return Err(ServiceError::BadRequest(hint));
+        }
+    };
+    if !contest.settings.allow_hacks {
+        let hint = "Contest does not allow hacks.".to_string();
+        return Err(ServiceError::BadRequest(hint));
+    }
+    if get_contest_state(contest, get_cur_naive_date_time()) == ContestState::Preparing {
+        let hint = "Contest has not started.".to_string();
+        return Err(ServiceError::BadRequest(hint));
+    }
+    check_acl(conn, user_id, region.clone())?;
+
+    if target.user_id == user_id {
+        let hint = "Can not hack your own submission.".to_string();
+        return Err(ServiceError::BadRequest(hint));
+    }
+    if target.is_accepted != Some(true) {
+        let hint = "Only accepted submissions can be hacked.".to_string();
+        return Err(ServiceError::BadRequest(hint));
+    }
+
+    // like Codeforces, only those who solved the problem may hack it
+    let solved_count: i64 = submissions_schema::table
+        .filter(submissions_schema::region.eq(region.clone()))
+        .filter(submissions_schema::problem_id.eq(target.problem_id))
+        .filter(submissions_schema::user_id.eq(user_id))
+        .filter(submissions_schema::is_accepted.eq(true))
+        .count()
+        .get_result(conn)?;
+    if solved_count == 0 {
+        let hint = "Solve the problem before hacking it.".to_string();
+        return Err(ServiceError::BadRequest(hint));
+    }
+
+    use crate::schema::problems as problems_schema;
+    let raw_problem: problems::RawProblem = problems_schema::table
+        .filter(problems_schema::id.eq(target.problem_id))
+        .first(conn)?;
+    let problem = problems::Problem::from(raw_problem);
+    if problem.settings.is_interactive || problem.settings.std_language.is_none() {
+        let hint = "Problem does not support hacks.".to_string();
+        return Err(ServiceError::BadRequest(hint));
+    }
+
+    let id = Uuid::new_v4();
+    use crate::schema::hacks as hacks_schema;
+    diesel::insert_into(hacks_schema::table)
+        .values(&Hack {
+            id,
+            submission_id,
+            region,
+            problem_id: target.problem_id,
+            hacker_id: user_id,
+            defender_id: target.user_id,
+            input,
+            answer: None,
+            state: WAITING.to_owned(),
+            reason: None,
+            create_time: get_cur_naive_date_time(),
+            finish_time: None,
+            test_case_id: None,
+        })
+        .execute(conn)?;
+
+    judge_actor.addr.do_send(StartHack(id));
+
+    Ok(id)
+}
+
+pub fn get(id: Uuid, pool: web::Data<Pool>) -> ServiceResult<Hack> {
+    let conn = &db_connection(&pool)?;
+
+    use crate::schema::hacks as hacks_schema;
+    Ok(hacks_schema::table
+        .filter(hacks_schema::id.eq(id))
+        .first(conn)?)
+}
+
+pub fn get_list(
+    region_filter: Option<String>,
+    problem_id_filter: Option<i32>,
+    hacker_id_filter: Option<i32>,
+    defender_id_filter: Option<i32>,
+    limit: i32,
+    offset: i32,
+    pool: web::Data<Pool>,
+) -> ServiceResult<SizedList<SlimHack>> {
+    let conn = &db_connection(&pool)?;
+
+    use crate::schema::hacks as hacks_schema;
+    let target = hacks_schema::table
+        .filter(
+            hacks_schema::region
+                .nullable()
+                .eq(region_filter.clone())
+                .or(region_filter.is_none()),
+        )
+        .filter(
+            hacks_schema::problem_id
+                .nullable()
+                .eq(problem_id_filter)
+                .or(problem_id_filter.is_none()),
+        )
+        .filter(
+            hacks_schema::hacker_id
+                .nullable()
+                .eq(hacker_id_filter)
+                .or(hacker_id_filter.is_none()),
+        )
+        .filter(
+            hacks_schema::defender_id
+                .nullable()
+                .eq(defender_id_filter)
+                .or(defender_id_filter.is_none()),
+        );
+
+    let total: i64 = target.clone().count().get_result(conn)?;
+
+    let hacks: Vec<Hack> = target
+        .offset(offset.into())
+        .limit(limit.into())
+        .order(hacks_schema::create_time.desc())
+        .load(conn)?;
+
+    Ok(SizedList {
+        total,
+        list: hacks.into_iter().map(SlimHack::from).collect(),
+    })
+}
+
+// Keep the input of a successful hack as a new test case of the problem,
+// submissions pass or fail on it once they are rejudged.
+pub fn append_to_test_cases(id: Uuid, pool: web::Data<Pool>) -> ServiceResult<i32> {
+    let conn = &db_connection(&pool)?;
+
+    use crate::schema::hacks as hacks_schema;
+    let hack: Hack = hacks_schema::table
+        .filter(hacks_schema::id.eq(id))
+        .first(conn)?;
+    if hack.state != SUCCESSFUL {
+        let hint = "Only successful hacks can be added to test cases.".to_string();
+        return Err(ServiceError::BadRequest(hint));
+    }
+    if hack.test_case_id.is_some() {
+        let hint = "Hack is already added to test cases.".to_string();
+        return Err(ServiceError::BadRequest(hint));
+    }
+
+    use crate::schema::problems as problems_schema;
+    let raw_problem: problems::RawProblem = problems_schema::table
+        .filter(problems_schema::id.eq(hack.problem_id))
+        .first(conn)?;
+    let mut settings = problems::Problem::from(raw_problem).settings;
+
+    let test_case_id = append_test_case(
+        &problems::test_case_dir(hack.problem_id),
+        &mut settings,
+        &hack.input,
+        &hack.answer.unwrap_or_default(),
+    )?;
+
+    diesel::update(problems_schema::table.filter(problems_schema::id.eq(hack.problem_id)))
+        .set(problems_schema::settings.eq(serde_json::to_string(&settings).unwrap()))
+        .execute(conn)?;
+    diesel::update(hacks_schema::table.filter(hacks_schema::id.eq(id)))
+        .set(hacks_schema::test_case_id.eq(Some(test_case_id)))
+        .execute(conn)?;
+
+    Ok(test_case_id)
+}
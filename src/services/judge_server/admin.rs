@@ -0,0 +1,79 @@
+use crate::judge_actor::{handler::StartJudge, JudgeActorAddr};
+use crate::models::judge_servers::{self, JudgeServerRecord};
+use crate::statics::JUDGE_SERVER_INFOS;
+use actix_web::web;
+use diesel::prelude::*;
+use server_core::database::{db_connection, Pool};
+use server_core::errors::{ServiceError, ServiceResult};
+
+pub fn set_state(
+    service_url: String,
+    state: &str,
+    pool: web::Data<Pool>,
+    judge_actor: web::Data<JudgeActorAddr>,
+) -> ServiceResult<()> {
+    let conn = &db_connection(&pool)?;
+
+    let is_known = {
+        let lock = JUDGE_SERVER_INFOS.read().unwrap();
+        lock.get(&service_url).is_some()
+    };
+
+    use crate::schema::judge_servers as judge_servers_schema;
+    if !is_known
+        && judge_servers_schema::table
+            .filter(judge_servers_schema::service_url.eq(service_url.clone()))
+            .count()
+            .get_result::<i64>(conn)?
+            == 0
+    {
+        let hint = "Judge server not found.".to_string();
+        return Err(ServiceError::BadRequest(hint));
+    }
+
+    diesel::insert_into(judge_servers_schema::table)
+        .values(&JudgeServerRecord {
+            service_url: service_url.clone(),
+            state: state.to_owned(),
+        })
+        .on_conflict(judge_servers_schema::service_url)
+        .do_update()
+        .set(judge_servers_schema::state.eq(state.to_owned()))
+        .execute(conn)?;
+
+    {
+        let mut lock = JUDGE_SERVER_INFOS.write().unwrap();
+        if let Some(info) = lock.get_mut(&service_url) {
+            info.state = state.to_owned();
+            // enabling a server by hand trusts it again without probes
+            if state == judge_servers::ACTIVE {
+                info.health = Default::default();
+            }
+        }
+    }
+
+    if state == judge_servers::ACTIVE {
+        judge_actor.addr.do_send(StartJudge());
+    }
+
+    Ok(())
+}
+
+pub fn forget(service_url: String, pool: web::Data<Pool>) -> ServiceResult<()> {
+    let conn = &db_connection(&pool)?;
+
+    use crate::schema::judge_servers as judge_servers_schema;
+    diesel::delete(
+        judge_servers_schema::table
+            .filter(judge_servers_schema::service_url.eq(service_url.clone())),
+    )
+    .execute(conn)?;
+
+    let mut lock = JUDGE_SERVER_INFOS.write().unwrap();
+    if lock.remove(&service_url).is_none() {
+        let hint = "Judge server not found.".to_string();
+        return Err(ServiceError::BadRequest(hint));
+    }
+
+    Ok(())
+}
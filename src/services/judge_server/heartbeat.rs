@@ -1,31 +1,38 @@
 use crate::judge_actor::{handler::StartJudge, JudgeActorAddr};
-use crate::models::judge_servers::JudgeServerInfo;
+use crate::models::judge_servers::{self, JudgeServerInfo};
 use crate::statics::JUDGE_SERVER_INFOS;
 use actix_web::client::Client;
 use actix_web::web;
+use diesel::prelude::*;
+use server_core::database::{db_connection, Pool};
 use server_core::errors::ServiceResult;
 use std::time::SystemTime;
 
 pub async fn record_server_info(
-    judger_version: String,
-    hostname: String,
-    cpu_core: i32,
-    memory: f32,
-    cpu: f32,
-    service_url: Option<String>,
+    body: judge_servers::HeartbeatBody,
     token: String,
     judge_actor: web::Data<JudgeActorAddr>,
+    pool: web::Data<Pool>,
 ) -> ServiceResult<()> {
+    let judge_servers::HeartbeatBody {
+        judger_version,
+        hostname,
+        cpu_core,
+        memory,
+        cpu,
+        service_url,
+        compiler_versions,
+    } = body;
     if !service_url.is_none() {
         let url = service_url.clone().unwrap();
-        let task_number = {
+        let is_known = {
             let lock = JUDGE_SERVER_INFOS.read().unwrap();
-            if lock.get(&url).is_none() {
-                0
-            } else {
-                let target = lock.get(&url).unwrap();
-                target.task_number
-            }
+            lock.get(&url).is_some()
+        };
+        let state = if is_known {
+            judge_servers::ACTIVE.to_owned()
+        } else {
+            load_state(url.clone(), pool).await?
         };
 
         let response = Client::new()
@@ -45,25 +52,54 @@ pub async fn record_server_info(
         };
 
         let now = SystemTime::now();
-        let judge_server_info = JudgeServerInfo {
+        let mut judge_server_info = JudgeServerInfo {
             judger_version: judger_version.clone(),
             hostname: hostname.clone(),
             cpu_core: cpu_core,
             memory: memory,
             cpu: cpu,
-            task_number: task_number,
-            service_url: url,
+            task_number: 0,
+            service_url: url.clone(),
             token: token.clone(),
             heartbeat_time: now,
             is_deprecated: is_deprecated,
+            state,
+            health: Default::default(),
+            compiler_versions,
         };
         let mut lock = JUDGE_SERVER_INFOS.write().unwrap();
+        // keep task_number, state and health of known servers, they may also have changed while pinging
+        if let Some(target) = lock.get(&url) {
+            judge_server_info.task_number = target.task_number;
+            judge_server_info.state = target.state.clone();
+            judge_server_info.health = target.health.clone();
+        }
+        let state = judge_server_info.state.clone();
         lock.insert(service_url.clone().unwrap(), judge_server_info);
 
-        if !is_deprecated {
+        if !is_deprecated && state != judge_servers::DISABLED {
             judge_actor.addr.do_send(StartJudge());
         }
     }
 
     Ok(())
 }
+
+// servers never seen before are active
+async fn load_state(service_url: String, pool: web::Data<Pool>) -> ServiceResult<String> {
+    let state = web::block(move || {
+        let conn = &db_connection(&pool)?;
+
+        use crate::schema::judge_servers as judge_servers_schema;
+        let state: Option<String> = judge_servers_schema::table
+            .filter(judge_servers_schema::service_url.eq(service_url))
+            .select(judge_servers_schema::state)
+            .first(conn)
+            .optional()?;
+
+        Ok(state)
+    })
+    .await?;
+
+    Ok(state.unwrap_or_else(|| judge_servers::ACTIVE.to_owned()))
+}
@@ -1,5 +1,14 @@
+mod admin;
+pub(crate) use admin::{forget, set_state};
+
 mod heartbeat;
 pub(crate) use heartbeat::record_server_info;
 
 mod info;
 pub(crate) use info::server_info;
+
+mod queue;
+pub(crate) use queue::queue_depth;
+
+mod token;
+pub(crate) use token::check_heartbeat;
@@ -0,0 +1,11 @@
+use crate::judge_actor::queue;
+use crate::models::judge_queue::QueueDepth;
+use actix_web::web;
+use server_core::database::{db_connection, Pool};
+use server_core::errors::ServiceResult;
+
+pub fn queue_depth(pool: web::Data<Pool>) -> ServiceResult<Vec<QueueDepth>> {
+    let conn = &db_connection(&pool)?;
+
+    queue::depth(conn)
+}
@@ -0,0 +1,51 @@
+use crate::models::languages::{load_languages, OutLanguage};
+use crate::statics::{JUDGE_SERVER_INFOS, LANGUAGES};
+use server_core::errors::{ServiceError, ServiceResult};
+use std::collections::BTreeSet;
+
+// Enabled languages, with the distinct compiler versions reported by the judge servers.
+pub fn get_list() -> ServiceResult<Vec<OutLanguage>> {
+    let servers = JUDGE_SERVER_INFOS.read().unwrap();
+    let registry = LANGUAGES.read().unwrap();
+
+    let languages = registry
+        .languages
+        .iter()
+        .filter(|language| language.enabled)
+        .map(|language| {
+            let compiler_versions: BTreeSet<String> = servers
+                .values()
+                .filter(|server| !server.is_deprecated)
+                .filter_map(|server| server.compiler_versions.get(&language.id).cloned())
+                .collect();
+            OutLanguage {
+                id: language.id.clone(),
+                name: language.name.clone(),
+                compile_command: language.compile.compile_command.clone(),
+                run_command: language.run.command.clone(),
+                compiler_versions: compiler_versions.into_iter().collect(),
+            }
+        })
+        .collect();
+
+    Ok(languages)
+}
+
+// Read languages.toml again, the registry in use is kept when the file is broken.
+// Returns the ids of the languages now accepted.
+pub fn reload() -> ServiceResult<Vec<String>> {
+    let registry = load_languages().map_err(|e| {
+        let hint = format!("Invalid languages.toml: {}", e);
+        ServiceError::BadRequest(hint)
+    })?;
+
+    let ids: Vec<String> = registry
+        .languages
+        .iter()
+        .map(|language| language.id.clone())
+        .collect();
+    *LANGUAGES.write().unwrap() = registry;
+    info!("Reloaded languages: {}", ids.join(", "));
+
+    Ok(ids)
+}
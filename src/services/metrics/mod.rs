@@ -0,0 +1,84 @@
+use crate::judge_actor::queue;
+use crate::metrics::*;
+use crate::models::metrics::SubmissionStateCount;
+use crate::statics::JUDGE_SERVER_INFOS;
+use actix_web::web;
+use diesel::prelude::*;
+use server_core::database::{db_connection, Pool};
+use server_core::errors::{ServiceError, ServiceResult};
+use std::time::SystemTime;
+use subtle::ConstantTimeEq;
+
+// Scrapers send `Authorization: Bearer <token>`, metrics are off when no token is configured.
+pub fn check_token(configured: Option<&str>, authorization: Option<&str>) -> ServiceResult<()> {
+    let configured = match configured {
+        Some(configured) => configured,
+        None => {
+            let hint = "Metrics are disabled.".to_string();
+            return Err(ServiceError::UnauthorizedWithHint(hint));
+        }
+    };
+
+    // exact values in constant time, so the token can not be guessed byte by byte
+    let matches = |token: &str| -> bool { token.as_bytes().ct_eq(configured.as_bytes()).into() };
+    match authorization.and_then(|authorization| authorization.strip_prefix("Bearer ")) {
+        Some(token) if matches(token) => Ok(()),
+        _ => {
+            let hint = "Invalid metrics token.".to_string();
+            Err(ServiceError::UnauthorizedWithHint(hint))
+        }
+    }
+}
+
+// Gauges describing the current state are refreshed here, counters and histograms
+// are updated where things happen.
+pub fn render(pool: web::Data<Pool>) -> ServiceResult<String> {
+    let pool_state = pool.state();
+    DB_POOL_CONNECTIONS.set(pool_state.connections as i64);
+    DB_POOL_IDLE_CONNECTIONS.set(pool_state.idle_connections as i64);
+    DB_POOL_MAX_SIZE.set(pool.max_size() as i64);
+
+    let conn = &db_connection(&pool)?;
+
+    QUEUE_DEPTH.reset();
+    for depth in queue::depth(conn)? {
+        QUEUE_DEPTH
+            .with_label_values(&[&depth.lane, "waiting"])
+            .set(depth.waiting);
+        QUEUE_DEPTH
+            .with_label_values(&[&depth.lane, "judging"])
+            .set(depth.judging);
+    }
+
+    let state_counts: Vec<SubmissionStateCount> =
+        diesel::sql_query("SELECT state, COUNT(*) AS count FROM submissions GROUP BY state")
+            .load(conn)?;
+    SUBMISSION_STATES.reset();
+    for state_count in state_counts {
+        SUBMISSION_STATES
+            .with_label_values(&[&state_count.state])
+            .set(state_count.count);
+    }
+
+    SERVER_TASK_NUMBER.reset();
+    SERVER_QUARANTINED.reset();
+    SERVER_HEARTBEAT_AGE.reset();
+    let now = SystemTime::now();
+    for info in JUDGE_SERVER_INFOS.read().unwrap().values() {
+        SERVER_TASK_NUMBER
+            .with_label_values(&[&info.service_url, &info.state])
+            .set(info.task_number as i64);
+        SERVER_QUARANTINED
+            .with_label_values(&[&info.service_url])
+            .set(info.health.is_quarantined() as i64);
+        let age = now
+            .duration_since(info.heartbeat_time)
+            .unwrap_or_default()
+            .as_secs();
+        SERVER_HEARTBEAT_AGE
+            .with_label_values(&[&info.service_url])
+            .set(age as i64);
+    }
+
+    Ok(encode())
+}
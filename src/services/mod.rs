@@ -1,9 +1,13 @@
 pub mod contest;
+pub mod hack;
 pub mod judge_server;
+pub mod language;
+pub mod metrics;
 pub mod problem;
 pub mod problem_set;
 pub mod rank;
 pub mod region;
+pub mod rejudge;
 pub mod sample;
 pub mod submission;
 pub mod user;
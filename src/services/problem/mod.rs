@@ -1,4 +1,4 @@
-mod utils;
+pub mod utils;
 
 use crate::models::problems::*;
 use crate::models::utils::SizedList;
@@ -41,10 +41,9 @@ pub fn batch_create(
                     serde_json::from_str(&insertable_problem.settings).unwrap();
                 match utils::prepare_test_cases(
                     &(dir.path().into_os_string().into_string().unwrap() + "/TestCases"),
-                    settings.is_spj,
+                    &mut settings,
                 ) {
-                    Ok(test_case_count) => {
-                        settings.test_case_count = Some(test_case_count);
+                    Ok(()) => {
                         target_problem.settings = serde_json::to_string(&settings).unwrap();
                         info!("{:?}", target_problem);
 
@@ -61,12 +60,12 @@ pub fn batch_create(
                                     .select(problems_schema::id)
                                     .first(conn)?;
 
-                                fs::remove_dir_all(format!("data/test_cases/{}", id)).unwrap_or({});
+                                fs::remove_dir_all(test_case_dir(id)).unwrap_or({});
 
                                 fs::rename(
                                     &(dir.path().into_os_string().into_string().unwrap()
                                         + "/TestCases"),
-                                    format!("data/test_cases/{}", id),
+                                    test_case_dir(id),
                                 )?;
 
                                 res.push(CreateProblemsResult {
@@ -331,7 +330,7 @@ pub fn delete(id: i32, pool: web::Data<Pool>) -> ServiceResult<()> {
     ))
     .execute(conn)?;
 
-    fs::remove_dir_all(&format!("data/test_cases/{}", id))?;
+    fs::remove_dir_all(test_case_dir(id))?;
 
     Ok(())
 }
@@ -339,9 +338,12 @@ pub fn delete(id: i32, pool: web::Data<Pool>) -> ServiceResult<()> {
 pub fn create(
     info: ProblemInfo,
     contents: ProblemContents,
-    settings: ProblemSettings,
+    mut settings: ProblemSettings,
     pool: web::Data<Pool>,
 ) -> ServiceResult<()> {
+    settings.upgrade_limits();
+    utils::check_language_limits(&settings)?;
+
     let conn = &db_connection(&pool)?;
 
     use crate::schema::problems as problems_schema;
@@ -363,12 +365,35 @@ pub fn update(
     id: i32,
     new_info: Option<ProblemInfo>,
     new_contents: Option<ProblemContents>,
-    new_settings: Option<ProblemSettings>,
+    mut new_settings: Option<ProblemSettings>,
     pool: web::Data<Pool>,
 ) -> ServiceResult<()> {
     let conn = &db_connection(&pool)?;
 
     use crate::schema::problems as problems_schema;
+    if let Some(settings) = new_settings.as_mut() {
+        let raw_settings: String = problems_schema::table
+            .filter(problems_schema::id.eq(id))
+            .select(problems_schema::settings)
+            .first(conn)?;
+        let stored_settings: ProblemSettings =
+            serde_json::from_str(&raw_settings).map_err(|_| {
+                let hint = format!("Broken settings in problem {}.", id);
+                ServiceError::InternalServerErrorWithHint(hint)
+            })?;
+        settings.keep_imported(&stored_settings);
+
+        settings.upgrade_limits();
+        utils::check_comparator(settings)?;
+        utils::check_language_limits(settings)?;
+        // scores of problems without test data yet are checked on import
+        let count = settings.test_case_count.unwrap_or(0);
+        if settings.test_case_count.is_some() {
+            utils::check_test_case_scores(settings.test_case_scores.as_deref(), count)?;
+        }
+        utils::check_subtasks(settings.subtasks.as_deref(), count)?;
+    }
+
     diesel::update(problems_schema::table.filter(problems_schema::id.eq(id)))
         .set(ProblemForm {
             title: if let Some(inner_data) = new_info.clone() {
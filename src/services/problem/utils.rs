@@ -1,4 +1,4 @@
-use crate::models::problems;
+use crate::models::{languages, problems};
 use digest::Digest;
 use hex::ToHex;
 use md5::Md5;
@@ -6,13 +6,21 @@ use server_core::errors::{ServiceError, ServiceResult};
 use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::prelude::*;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// compiling or running a checker or a validator takes no longer than this
+const HELPER_TIMEOUT: Duration = Duration::from_secs(30);
 
 fn read_settings(path: &str) -> std::io::Result<problems::ProblemSettings> {
     let mut file = File::open(path)?;
     let mut contents = String::new();
     file.read_to_string(&mut contents)?;
 
-    let settings: problems::ProblemSettings = toml::from_str(&contents)?;
+    let mut settings: problems::ProblemSettings = toml::from_str(&contents)?;
+    settings.upgrade_limits();
 
     Ok(settings)
 }
@@ -123,15 +131,46 @@ struct NormalTestCaseInfo {
     output_name: String,
     output_size: i32,
     stripped_output_md5: String,
+    score: i32,
 }
 
 #[derive(Debug, Clone, Serialize)]
-struct SpjTestCaseInfo {
+struct InteractiveTestCaseInfo {
     input_name: String,
     input_size: i32,
+    output_name: Option<String>,
+    score: i32,
+}
+
+fn get_test_case_score(scores: Option<&[i32]>, index: usize) -> i32 {
+    scores
+        .and_then(|scores| scores.get(index).copied())
+        .unwrap_or(1)
 }
 
-fn prepare_normal_test_cases(path: &str) -> ServiceResult<i32> {
+pub fn check_test_case_scores(scores: Option<&[i32]>, count: i32) -> ServiceResult<()> {
+    if let Some(scores) = scores {
+        if scores.len() != count as usize {
+            let hint = format!(
+                "Got {} test case scores for {} test cases.",
+                scores.len(),
+                count
+            );
+            return Err(ServiceError::BadRequest(hint));
+        }
+        if scores.iter().any(|score| *score < 0) || scores.iter().sum::<i32>() == 0 {
+            let hint = String::from("Test case scores must be non-negative and not all zero.");
+            return Err(ServiceError::BadRequest(hint));
+        }
+    }
+
+    Ok(())
+}
+
+// Special judged problems share the layout, checkers get the answers as well.
+// Checkers run in this app on the outputs judge servers send back,
+// so the servers are never told about them.
+fn prepare_normal_test_cases(path: &str, scores: Option<&[i32]>) -> ServiceResult<i32> {
     let mut count = 0;
     let mut test_cases: BTreeMap<String, NormalTestCaseInfo> = BTreeMap::new();
 
@@ -167,6 +206,7 @@ fn prepare_normal_test_cases(path: &str) -> ServiceResult<i32> {
                 output_name: output_name,
                 output_size: output_content.len() as i32,
                 stripped_output_md5: get_stripped_md5_output(&output_content),
+                score: get_test_case_score(scores, count as usize),
             },
         );
 
@@ -177,6 +217,7 @@ fn prepare_normal_test_cases(path: &str) -> ServiceResult<i32> {
         let hint = String::from("Need at least one test case.");
         return Err(ServiceError::BadRequest(hint));
     }
+    check_test_case_scores(scores, count)?;
 
     let info = serde_json::json!({
         "test_case_number": count,
@@ -190,30 +231,36 @@ fn prepare_normal_test_cases(path: &str) -> ServiceResult<i32> {
     Ok(count)
 }
 
-fn prepare_spj_test_case(path: &str) -> ServiceResult<i32> {
+// The answer files are optional, they are only handed to the interactor.
+fn prepare_interactive_test_cases(path: &str, scores: Option<&[i32]>) -> ServiceResult<i32> {
+    File::open(path.to_string() + "/interactor_src.cpp")?;
+
     let mut count = 0;
-    let mut test_cases: BTreeMap<String, SpjTestCaseInfo> = BTreeMap::new();
+    let mut test_cases: BTreeMap<String, InteractiveTestCaseInfo> = BTreeMap::new();
 
     loop {
-        // check if spj_src.c exists
-        if count == 0 {
-            File::open(path.to_string() + "/spj_src.c")?;
-        }
-        let name = (count + 1).to_string() + ".in";
-        let mut file = match File::open(path.to_string() + "/" + &name) {
+        let id = count + 1;
+        let input_name = id.to_string() + ".in";
+        let mut input_file = match File::open(path.to_string() + "/" + &input_name) {
             Ok(file) => file,
             Err(_) => {
-                info!("Can't find file {}", path.to_string() + "/" + &name);
+                info!("Can't find file {}", path.to_string() + "/" + &input_name);
                 break;
             }
         };
-        let mut content = String::new();
-        file.read_to_string(&mut content).unwrap();
+        let mut input_content = String::new();
+        input_file.read_to_string(&mut input_content)?;
+
+        let output_name = id.to_string() + ".out";
+        let has_output = File::open(path.to_string() + "/" + &output_name).is_ok();
+
         test_cases.insert(
-            count.to_string(),
-            SpjTestCaseInfo {
-                input_name: name,
-                input_size: content.len() as i32,
+            id.to_string(),
+            InteractiveTestCaseInfo {
+                input_name,
+                input_size: input_content.len() as i32,
+                output_name: if has_output { Some(output_name) } else { None },
+                score: get_test_case_score(scores, count as usize),
             },
         );
 
@@ -224,24 +271,332 @@ fn prepare_spj_test_case(path: &str) -> ServiceResult<i32> {
         let hint = String::from("Need at least one test case.");
         return Err(ServiceError::BadRequest(hint));
     }
+    check_test_case_scores(scores, count)?;
 
     let info = serde_json::json!({
         "test_case_number": count,
-        "spj": true,
+        "spj": false,
+        "interactive": true,
         "test_cases": test_cases,
     });
 
-    let mut file = File::create(path.to_string() + "/" + "info").expect("Error creating info");
-    file.write_all(info.to_string().as_bytes())
-        .expect("Error writing info");
+    let mut file = File::create(&(path.to_string() + "/info"))?;
+    file.write_all(info.to_string().as_bytes())?;
 
     Ok(count)
 }
 
-pub fn prepare_test_cases(path: &str, is_spj: bool) -> ServiceResult<i32> {
-    if is_spj {
-        Ok(prepare_spj_test_case(path)?)
+pub fn check_comparator(settings: &problems::ProblemSettings) -> ServiceResult<()> {
+    if (settings.is_spj || settings.is_interactive)
+        && settings.comparator != problems::Comparator::Stripped
+    {
+        let hint =
+            String::from("Comparators do not apply to special judged or interactive problems.");
+        return Err(ServiceError::BadRequest(hint));
+    }
+    if let problems::Comparator::Float { abs_eps, rel_eps } = settings.comparator {
+        // also refuses NaN
+        if !(abs_eps >= 0.0 && rel_eps >= 0.0) {
+            let hint = String::from("Epsilons of the float comparator must be non-negative.");
+            return Err(ServiceError::BadRequest(hint));
+        }
+    }
+
+    Ok(())
+}
+
+pub fn check_language_limits(settings: &problems::ProblemSettings) -> ServiceResult<()> {
+    if !(settings.max_cpu_time > 0 && settings.max_memory > 0) {
+        let hint = String::from("Base limits must be positive.");
+        return Err(ServiceError::BadRequest(hint));
+    }
+    for (language, limits) in settings.language_limits.iter() {
+        languages::check_known_language(language)?;
+        limits.check().map_err(|e| {
+            let hint = format!("Limits of {}: {}", language, e);
+            ServiceError::BadRequest(hint)
+        })?;
+    }
+
+    Ok(())
+}
+
+pub fn check_subtasks(subtasks: Option<&[problems::Subtask]>, count: i32) -> ServiceResult<()> {
+    let subtasks = match subtasks {
+        Some(subtasks) => subtasks,
+        None => return Ok(()),
+    };
+
+    for (index, subtask) in subtasks.iter().enumerate() {
+        let id = index as i32 + 1;
+        let (first, last) = subtask.range;
+        if first < 1 || first > last || last > count {
+            let hint = format!(
+                "Subtask {} covers test cases {} to {}, but there are {} test cases.",
+                id, first, last, count
+            );
+            return Err(ServiceError::BadRequest(hint));
+        }
+        if subtask.score < 0 {
+            let hint = format!("Subtask {} has a negative score.", id);
+            return Err(ServiceError::BadRequest(hint));
+        }
+        // only earlier subtasks can be depended on, so there is no cycle
+        if let Some(dependency) = subtask
+            .dependencies
+            .iter()
+            .find(|dependency| **dependency < 1 || **dependency >= id)
+        {
+            let hint = format!("Subtask {} can not depend on subtask {}.", id, dependency);
+            return Err(ServiceError::BadRequest(hint));
+        }
+    }
+
+    Ok(())
+}
+
+// Runs a command of the import or of a helper compiled on import, which is not sandboxed,
+// so only trusted packages are imported.
+pub fn run_with_timeout(
+    command: &str,
+    dir: &Path,
+    stdin: Option<&Path>,
+    timeout: Duration,
+) -> ServiceResult<(bool, String)> {
+    let args: Vec<&str> = command.split_whitespace().collect();
+    if args.is_empty() {
+        let hint = "Empty helper command.".to_owned();
+        return Err(ServiceError::InternalServerErrorWithHint(hint));
+    }
+    let mut child = Command::new(args[0])
+        .args(&args[1..])
+        .current_dir(dir)
+        .stdin(match stdin {
+            Some(stdin) => Stdio::from(File::open(stdin)?),
+            None => Stdio::null(),
+        })
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break Some(status);
+        }
+        if started.elapsed() > timeout {
+            child.kill().unwrap_or(());
+            child.wait()?;
+            break None;
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    let mut message = String::new();
+    if let Some(mut stderr) = child.stderr.take() {
+        stderr.read_to_string(&mut message).unwrap_or(0);
+    }
+    match status {
+        Some(status) => Ok((status.success(), message)),
+        None => Ok((false, message + "\nTimed out.")),
+    }
+}
+
+fn find_source(
+    dir: &Path,
+    sources: &[(&'static str, &'static str)],
+) -> Option<(&'static str, &'static str)> {
+    sources
+        .iter()
+        .find(|(src_name, _)| dir.join(src_name).is_file())
+        .copied()
+}
+
+// Compile a checker or a validator into `exe_name` next to its source.
+fn compile_helper(dir: &Path, src_name: &str, language: &str, exe_name: &str) -> ServiceResult<()> {
+    let vars = [
+        (
+            "{src_path}",
+            dir.join(src_name).to_string_lossy().to_string(),
+        ),
+        ("{exe_dir}", dir.to_string_lossy().to_string()),
+        (
+            "{exe_path}",
+            dir.join(exe_name).to_string_lossy().to_string(),
+        ),
+    ];
+    let mut compile_command = languages::checker_compile_command(language);
+    for (name, value) in vars.iter() {
+        compile_command = compile_command.replace(name, value);
+    }
+    let (is_compiled, message) = run_with_timeout(&compile_command, dir, None, HELPER_TIMEOUT)?;
+    if !is_compiled {
+        let hint = format!("Failed to compile {}: {}", src_name, message);
+        return Err(ServiceError::BadRequest(hint));
+    }
+
+    Ok(())
+}
+
+// Compile the checker in TestCases and make sure it accepts the answers themselves,
+// returns the language it is written in.
+fn prepare_checker(path: &str, count: i32) -> ServiceResult<String> {
+    let dir = Path::new(path);
+    let (src_name, language) = match find_source(dir, &languages::CHECKER_SOURCES) {
+        Some(checker_source) => checker_source,
+        None => {
+            let hint =
+                String::from("Need a checker.c, checker.cpp or checker.py for special judge.");
+            return Err(ServiceError::BadRequest(hint));
+        }
+    };
+    compile_helper(dir, src_name, language, "checker")?;
+
+    let checker_command = languages::checker_config(language)
+        .command
+        .replace("{exe_dir}", &dir.to_string_lossy());
+    for id in 1..=count {
+        let input_path = dir.join(format!("{}.in", id)).to_string_lossy().to_string();
+        let answer_path = dir
+            .join(format!("{}.out", id))
+            .to_string_lossy()
+            .to_string();
+        let command = checker_command
+            .replace("{in_file_path}", &input_path)
+            .replace("{user_out_file_path}", &answer_path)
+            .replace("{answer_file_path}", &answer_path);
+        let (is_accepted, message) = run_with_timeout(&command, dir, None, HELPER_TIMEOUT)?;
+        if !is_accepted {
+            let hint = format!(
+                "Checker rejects the answer of test case {}: {}",
+                id, message
+            );
+            return Err(ServiceError::BadRequest(hint));
+        }
+    }
+
+    Ok(language.to_owned())
+}
+
+// Run the validator of the problem in `path` on an input, Err holds why it is invalid.
+pub fn validate_input(
+    path: &str,
+    language: &str,
+    input_path: &Path,
+) -> ServiceResult<Result<(), String>> {
+    let dir = Path::new(path);
+    let command =
+        languages::validator_command(language).replace("{exe_dir}", &dir.to_string_lossy());
+    let (is_valid, message) = run_with_timeout(&command, dir, Some(input_path), HELPER_TIMEOUT)?;
+    Ok(if is_valid { Ok(()) } else { Err(message) })
+}
+
+// The validator is optional, when there is one it must accept every input of the test data.
+fn prepare_validator(path: &str, count: i32) -> ServiceResult<Option<String>> {
+    let dir = Path::new(path);
+    let (src_name, language) = match find_source(dir, &languages::VALIDATOR_SOURCES) {
+        Some(validator_source) => validator_source,
+        None => return Ok(None),
+    };
+    compile_helper(dir, src_name, language, "validator")?;
+
+    for id in 1..=count {
+        if let Err(message) = validate_input(path, language, &dir.join(format!("{}.in", id)))? {
+            let hint = format!(
+                "Validator rejects the input of test case {}: {}",
+                id, message
+            );
+            return Err(ServiceError::BadRequest(hint));
+        }
+    }
+
+    Ok(Some(language.to_owned()))
+}
+
+// Digest of every file in TestCases but those generated on import.
+fn test_data_version(path: &str) -> ServiceResult<String> {
+    let mut entries: Vec<std::fs::DirEntry> = std::fs::read_dir(path)?.collect::<Result<_, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    let mut hasher = Md5::new();
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !entry.file_type()?.is_file()
+            || ["info", "checker", "validator"].contains(&name.as_str())
+        {
+            continue;
+        }
+        hasher.update(name.as_bytes());
+        hasher.update(std::fs::read(entry.path())?);
+    }
+    Ok(hasher.finalize().encode_hex::<String>())
+}
+
+// Fills `test_case_count` and the languages of helpers found in TestCases.
+pub fn prepare_test_cases(
+    path: &str,
+    settings: &mut problems::ProblemSettings,
+) -> ServiceResult<()> {
+    if settings.is_spj && settings.is_interactive {
+        let hint = String::from("A problem can not be both special judged and interactive.");
+        return Err(ServiceError::BadRequest(hint));
+    }
+    check_comparator(settings)?;
+    check_language_limits(settings)?;
+
+    let scores = settings.test_case_scores.as_deref();
+    let count = if settings.is_interactive {
+        prepare_interactive_test_cases(path, scores)?
     } else {
-        Ok(prepare_normal_test_cases(path)?)
+        prepare_normal_test_cases(path, scores)?
+    };
+    check_subtasks(settings.subtasks.as_deref(), count)?;
+
+    settings.checker_language = if settings.is_spj {
+        Some(prepare_checker(path, count)?)
+    } else {
+        None
+    };
+    settings.validator_language = prepare_validator(path, count)?;
+    // it runs like a submission, so it only has to be there
+    settings.std_language = find_source(Path::new(path), &languages::STD_SOURCES)
+        .map(|(_, language)| language.to_owned());
+    settings.test_data_version = Some(test_data_version(path)?);
+    settings.test_case_count = Some(count);
+
+    Ok(())
+}
+
+// Append a test case to an imported problem, which is how successful hacks are kept.
+// Test data stays as it was when the new test case is rejected.
+pub fn append_test_case(
+    path: &str,
+    settings: &mut problems::ProblemSettings,
+    input: &str,
+    answer: &str,
+) -> ServiceResult<i32> {
+    if settings.is_interactive {
+        let hint = String::from("Can not append test cases to an interactive problem.");
+        return Err(ServiceError::BadRequest(hint));
+    }
+
+    let id = settings.test_case_count.unwrap_or(0) + 1;
+    let input_path = format!("{}/{}.in", path, id);
+    let answer_path = format!("{}/{}.out", path, id);
+    File::create(&input_path)?.write_all(input.as_bytes())?;
+    File::create(&answer_path)?.write_all(answer.as_bytes())?;
+
+    let old_settings = settings.clone();
+    if let Some(scores) = settings.test_case_scores.as_mut() {
+        scores.push(1);
+    }
+    if let Err(e) = prepare_test_cases(path, settings) {
+        std::fs::remove_file(&input_path).unwrap_or(());
+        std::fs::remove_file(&answer_path).unwrap_or(());
+        *settings = old_settings;
+        prepare_test_cases(path, settings)?;
+        return Err(e);
     }
+
+    Ok(id)
 }
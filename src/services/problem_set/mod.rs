@@ -24,6 +24,7 @@ pub fn create(
             title: title.clone(),
             has_access_setting: false,
             introduction: introduction.clone(),
+            allowed_languages: None,
         })
         .execute(conn)?;
 
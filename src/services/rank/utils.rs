@@ -141,6 +141,12 @@ fn build_acm_rank_column(
             .load(conn)?;
 
         for submission in submissions {
+            if !contest.settings.compile_error_penalty
+                && submission.err.as_deref() == Some(COMPILE_ERROR_ERR)
+            {
+                continue;
+            }
+
             let submit_state = get_contest_state(contest.clone(), submission.submit_time);
 
             if submit_state == ContestState::Preparing {
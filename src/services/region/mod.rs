@@ -1,6 +1,7 @@
 pub mod utils;
 
 use crate::judge_actor::JudgeActorAddr;
+use crate::models::languages;
 use crate::models::problems::*;
 use crate::models::region_links::*;
 use crate::models::regions::*;
@@ -107,6 +108,7 @@ pub fn get_linked_problem_column_list(
     difficulty_order: Option<bool>,
     limit: i32,
     offset: i32,
+    user_id: Option<i32>,
     pool: web::Data<Pool>,
 ) -> ServiceResult<SizedList<LinkedProblemColumn>> {
     let conn = &db_connection(&pool)?;
@@ -177,6 +179,7 @@ pub fn get_linked_problem_column_list(
         problems_schema::tags,
         problems_schema::difficulty,
         problems_schema::is_released,
+        region_links_schema::score,
     ));
 
     let columns: Vec<RawLinkedProblemColumn> = match id_order {
@@ -202,7 +205,7 @@ pub fn get_linked_problem_column_list(
     let out_columns = {
         let mut res = Vec::new();
         for column in columns {
-            res.push(get_column_from_raw(conn, column)?);
+            res.push(get_column_from_raw(conn, column, user_id)?);
         }
         res
     };
@@ -268,6 +271,8 @@ pub fn create_submission(
         return Err(ServiceError::BadRequest(hint));
     }
 
+    utils::check_language_allowed(region.clone(), &language, conn)?;
+
     use crate::services::submission::create as inner_create;
 
     inner_create(
@@ -281,6 +286,33 @@ pub fn create_submission(
     )
 }
 
+// None lifts the restriction, an empty list blocks every language.
+pub fn set_allowed_languages(
+    region: String,
+    allowed_languages: Option<Vec<String>>,
+    pool: web::Data<Pool>,
+) -> ServiceResult<()> {
+    if let Some(allowed_languages) = &allowed_languages {
+        for language in allowed_languages.iter() {
+            languages::check_known_language(language)?;
+        }
+    }
+
+    let conn = &db_connection(&pool)?;
+
+    use crate::schema::regions as regions_schema;
+    let updated = diesel::update(regions_schema::table.filter(regions_schema::name.eq(region)))
+        .set(regions_schema::allowed_languages.eq(allowed_languages))
+        .execute(conn)?;
+
+    if updated == 0 {
+        let hint = "Region not found.".to_string();
+        return Err(ServiceError::BadRequest(hint));
+    }
+
+    Ok(())
+}
+
 pub fn delete_problem(region: String, inner_id: i32, pool: web::Data<Pool>) -> ServiceResult<()> {
     let conn = &db_connection(&pool)?;
 
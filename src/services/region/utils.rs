@@ -1,5 +1,5 @@
 use diesel::prelude::*;
-use server_core::errors::ServiceResult;
+use server_core::errors::{ServiceError, ServiceResult};
 
 pub fn get_self_type(region: String, db_connection: &PgConnection) -> ServiceResult<String> {
     use crate::schema::regions as regions_schema;
@@ -9,3 +9,24 @@ pub fn get_self_type(region: String, db_connection: &PgConnection) -> ServiceRes
         .select(regions_schema::self_type)
         .first(db_connection)?)
 }
+
+pub fn check_language_allowed(
+    region: String,
+    language: &str,
+    db_connection: &PgConnection,
+) -> ServiceResult<()> {
+    use crate::schema::regions as regions_schema;
+
+    let allowed_languages: Option<Vec<String>> = regions_schema::table
+        .filter(regions_schema::name.eq(region))
+        .select(regions_schema::allowed_languages)
+        .first(db_connection)?;
+
+    match allowed_languages {
+        Some(allowed_languages) if !allowed_languages.iter().any(|allowed| allowed == language) => {
+            let hint = format!("Language {} is not allowed in this region.", language);
+            Err(ServiceError::BadRequest(hint))
+        }
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,225 @@
+use crate::judge_actor::{handler::StartJudge, queue, JudgeActorAddr};
+use crate::models::rejudges::*;
+use crate::models::submissions::{JudgeSettings, RawSubmission};
+use crate::services::submission::{build_settings, content_hash};
+use crate::statics::{ACM_RANK_CACHE, RESULT_STATISTICS_CACHE};
+use actix_web::web;
+use diesel::prelude::*;
+use server_core::database::{db_connection, Pool};
+use server_core::errors::{ServiceError, ServiceResult};
+use server_core::utils::time::get_cur_naive_date_time;
+use uuid::Uuid;
+
+fn sorted(out_results: Option<Vec<String>>) -> Option<Vec<String>> {
+    out_results.map(|mut out_results| {
+        out_results.sort();
+        out_results
+    })
+}
+
+pub fn create(
+    submission_id: Option<Uuid>,
+    problem_id: Option<i32>,
+    region: Option<String>,
+    pool: web::Data<Pool>,
+    judge_actor: web::Data<JudgeActorAddr>,
+) -> ServiceResult<RejudgeReport> {
+    let filter_count =
+        submission_id.is_some() as i32 + problem_id.is_some() as i32 + region.is_some() as i32;
+    if filter_count != 1 {
+        let hint = "Need exactly one of submission_id, problem_id and region.".to_string();
+        return Err(ServiceError::BadRequest(hint));
+    }
+
+    let conn = &db_connection(&pool)?;
+
+    use crate::schema::submissions as submissions_schema;
+    let raw_submissions: Vec<RawSubmission> = submissions_schema::table
+        .filter(
+            submissions_schema::id
+                .nullable()
+                .eq(submission_id)
+                .or(submission_id.is_none()),
+        )
+        .filter(
+            submissions_schema::problem_id
+                .nullable()
+                .eq(problem_id)
+                .or(problem_id.is_none()),
+        )
+        .filter(
+            submissions_schema::region
+                .eq(region.clone())
+                .or(region.is_none()),
+        )
+        // running submissions will be judged with the new data anyway
+        .filter(submissions_schema::state.ne("Waiting".to_owned()))
+        .filter(submissions_schema::state.ne("Pending".to_owned()))
+        .load(conn)?;
+
+    let batch_id = Uuid::new_v4();
+    let create_time = get_cur_naive_date_time();
+
+    let mut skipped = Vec::new();
+    let rejudged: Vec<&RawSubmission> = conn.transaction::<_, ServiceError, _>(|| {
+        use crate::schema::rejudge_records as rejudge_records_schema;
+        let mut rejudged = Vec::new();
+        for raw_submission in raw_submissions.iter() {
+            // one submission which can not be judged again must not hold back the others
+            let (settings, content_hash) = match rebuild_settings(conn, raw_submission) {
+                Ok(rebuilt) => rebuilt,
+                Err(e) => {
+                    log::warn!("Skipping rejudge of {}: {}", raw_submission.id, e);
+                    skipped.push(RejudgeSkip {
+                        submission_id: raw_submission.id,
+                        reason: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+
+            diesel::insert_into(rejudge_records_schema::table)
+                .values(&RejudgeRecord {
+                    batch_id,
+                    submission_id: raw_submission.id,
+                    create_time,
+                    old_is_accepted: raw_submission.is_accepted,
+                    old_err: raw_submission.err.clone(),
+                    old_out_results: sorted(raw_submission.out_results.clone()),
+                })
+                .execute(conn)?;
+
+            diesel::update(
+                submissions_schema::table.filter(submissions_schema::id.eq(raw_submission.id)),
+            )
+            .set((
+                submissions_schema::state.eq("Waiting".to_owned()),
+                submissions_schema::settings.eq(serde_json::to_string(&settings).unwrap()),
+                submissions_schema::result.eq(None::<String>),
+                submissions_schema::is_accepted.eq(None::<bool>),
+                submissions_schema::finish_time.eq(None::<chrono::NaiveDateTime>),
+                submissions_schema::max_time.eq(None::<i32>),
+                submissions_schema::max_memory.eq(None::<i32>),
+                submissions_schema::err.eq(None::<String>),
+                submissions_schema::out_results.eq(None::<Vec<String>>),
+                submissions_schema::score.eq(None::<i32>),
+                submissions_schema::content_hash.eq(content_hash),
+                submissions_schema::is_cached.eq(false),
+            ))
+            .execute(conn)?;
+
+            let lane = queue::lane_of(conn, raw_submission.region.as_deref())?;
+            queue::push(conn, raw_submission.id, lane)?;
+            rejudged.push(raw_submission);
+        }
+
+        Ok(rejudged)
+    })?;
+
+    // statistics and ranks are rebuilt from the database on next access
+    {
+        let mut result_statistics = RESULT_STATISTICS_CACHE.write().unwrap();
+        let mut acm_ranks = ACM_RANK_CACHE.write().unwrap();
+        for raw_submission in rejudged.iter() {
+            if let Some(region) = raw_submission.region.clone() {
+                result_statistics.remove(&(region.clone(), raw_submission.problem_id));
+                acm_ranks.remove(&region);
+            }
+        }
+    }
+
+    info!(
+        "Rejudging {} submissions in batch {}, skipped {}",
+        rejudged.len(),
+        batch_id,
+        skipped.len()
+    );
+    judge_actor.addr.do_send(StartJudge());
+
+    Ok(RejudgeReport {
+        batch_id,
+        create_time,
+        total: rejudged.len() as i32,
+        finished: 0,
+        changed: Vec::new(),
+        skipped,
+    })
+}
+
+// Settings and content hash a submission is judged again with.
+fn rebuild_settings(
+    conn: &PgConnection,
+    raw_submission: &RawSubmission,
+) -> ServiceResult<(JudgeSettings, Option<String>)> {
+    let old_settings =
+        serde_json::from_str::<JudgeSettings>(&raw_submission.settings).map_err(|_| {
+            let hint = format!("Broken settings in submission {}.", raw_submission.id);
+            ServiceError::InternalServerErrorWithHint(hint)
+        })?;
+    // limits and special judge may have been fixed together with the test data
+    let settings = match raw_submission.language.clone() {
+        Some(language) => {
+            build_settings(conn, raw_submission.problem_id, old_settings.src, &language)?
+        }
+        None => old_settings,
+    };
+    // rejudges are always judged again, but later submissions may reuse their results
+    let content_hash = content_hash(conn, raw_submission.problem_id, &settings)?;
+
+    Ok((settings, content_hash))
+}
+
+pub fn get_report(batch_id: Uuid, pool: web::Data<Pool>) -> ServiceResult<RejudgeReport> {
+    let conn = &db_connection(&pool)?;
+
+    use crate::schema::rejudge_records as rejudge_records_schema;
+    use crate::schema::submissions as submissions_schema;
+    let raw: Vec<(RejudgeRecord, RawSubmission)> = rejudge_records_schema::table
+        .inner_join(
+            submissions_schema::table
+                .on(rejudge_records_schema::submission_id.eq(submissions_schema::id)),
+        )
+        .filter(rejudge_records_schema::batch_id.eq(batch_id))
+        .load(conn)?;
+
+    if raw.is_empty() {
+        let hint = "Rejudge batch not found.".to_string();
+        return Err(ServiceError::BadRequest(hint));
+    }
+
+    let mut report = RejudgeReport {
+        batch_id,
+        create_time: raw[0].0.create_time,
+        total: raw.len() as i32,
+        finished: 0,
+        changed: Vec::new(),
+        skipped: Vec::new(),
+    };
+    for (record, raw_submission) in raw {
+        if raw_submission.state == "Waiting" || raw_submission.state == "Pending" {
+            continue;
+        }
+        report.finished += 1;
+
+        let new_out_results = sorted(raw_submission.out_results);
+        if record.old_is_accepted != raw_submission.is_accepted
+            || record.old_err != raw_submission.err
+            || record.old_out_results != new_out_results
+        {
+            report.changed.push(RejudgeChange {
+                submission_id: raw_submission.id,
+                problem_id: raw_submission.problem_id,
+                user_id: raw_submission.user_id,
+                region: raw_submission.region,
+                old_is_accepted: record.old_is_accepted,
+                new_is_accepted: raw_submission.is_accepted,
+                old_err: record.old_err,
+                new_err: raw_submission.err,
+                old_out_results: record.old_out_results,
+                new_out_results,
+            });
+        }
+    }
+
+    Ok(report)
+}
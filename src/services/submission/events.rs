@@ -0,0 +1,156 @@
+use crate::judge_actor::queue;
+use crate::models::submissions::{RawSubmission, Submission, SubmissionEvent};
+use crate::statics::SUBMISSION_SUBSCRIBERS;
+use actix_web::web;
+use diesel::prelude::*;
+use futures::channel::mpsc::{unbounded, UnboundedReceiver};
+use server_core::database::{db_connection, Pool};
+use server_core::errors::{ServiceError, ServiceResult};
+use uuid::Uuid;
+
+// submissions one stream may follow, each of them holds a subscriber entry
+pub const MAX_SUBSCRIBED_SUBMISSIONS: usize = 50;
+
+fn is_final(state: &str) -> bool {
+    state == "Finished" || state == "SystemError"
+}
+
+pub fn event_of(raw_submission: RawSubmission, queue_position: Option<usize>) -> SubmissionEvent {
+    let submission = Submission::from(raw_submission);
+    SubmissionEvent {
+        id: submission.id,
+        state: submission.state,
+        queue_position,
+        result: submission.result,
+        score: submission.score,
+        err: submission.err,
+    }
+}
+
+// Owners of the submissions to subscribe to, every one of them has to exist.
+pub fn owners(submission_ids: Vec<Uuid>, pool: web::Data<Pool>) -> ServiceResult<Vec<i32>> {
+    let conn = &db_connection(&pool)?;
+
+    use crate::schema::submissions as submissions_schema;
+    let user_ids: Vec<i32> = submissions_schema::table
+        .filter(submissions_schema::id.eq_any(submission_ids.clone()))
+        .select(submissions_schema::user_id)
+        .load(conn)?;
+
+    if user_ids.len() != submission_ids.len() {
+        let hint = "Submission not found.".to_string();
+        return Err(ServiceError::BadRequest(hint));
+    }
+
+    Ok(user_ids)
+}
+
+// Register before reading the current states, so no transition can be missed in between.
+// The stream ends once every subscribed submission reaches a final state.
+pub fn subscribe(
+    submission_ids: Vec<Uuid>,
+    pool: web::Data<Pool>,
+) -> ServiceResult<UnboundedReceiver<SubmissionEvent>> {
+    let conn = &db_connection(&pool)?;
+
+    let (sender, receiver) = unbounded();
+    {
+        let mut lock = SUBMISSION_SUBSCRIBERS.write().unwrap();
+        for submission_id in submission_ids.iter() {
+            lock.entry(*submission_id).or_default().push(sender.clone());
+        }
+    }
+
+    use crate::schema::submissions as submissions_schema;
+    let raw_submissions: Vec<RawSubmission> = submissions_schema::table
+        .filter(submissions_schema::id.eq_any(submission_ids))
+        .load(conn)?;
+    let waiting_ids = queue::waiting_ids(conn)?;
+
+    let mut finished_ids = Vec::new();
+    for raw_submission in raw_submissions {
+        if is_final(&raw_submission.state) {
+            finished_ids.push(raw_submission.id);
+        }
+        let queue_position = waiting_ids
+            .iter()
+            .position(|id| *id == raw_submission.id)
+            .map(|index| index + 1);
+        let _ = sender.unbounded_send(event_of(raw_submission, queue_position));
+    }
+
+    // the judge actor will never publish these again
+    {
+        let mut lock = SUBMISSION_SUBSCRIBERS.write().unwrap();
+        for submission_id in finished_ids {
+            lock.remove(&submission_id);
+        }
+    }
+
+    Ok(receiver)
+}
+
+pub fn publish(event: SubmissionEvent) {
+    let mut lock = SUBMISSION_SUBSCRIBERS.write().unwrap();
+    if let Some(senders) = lock.get_mut(&event.id) {
+        senders.retain(|sender| sender.unbounded_send(event.clone()).is_ok());
+        if senders.is_empty() || is_final(&event.state) {
+            lock.remove(&event.id);
+        }
+    }
+}
+
+// Load the submission after a transition and send it to its subscribers.
+pub fn publish_submission(conn: &PgConnection, submission_id: Uuid) -> ServiceResult<()> {
+    if !SUBMISSION_SUBSCRIBERS
+        .read()
+        .unwrap()
+        .contains_key(&submission_id)
+    {
+        return Ok(());
+    }
+
+    use crate::schema::submissions as submissions_schema;
+    let raw_submission: RawSubmission = submissions_schema::table
+        .filter(submissions_schema::id.eq(submission_id))
+        .first(conn)?;
+
+    let queue_position = if raw_submission.state == "Waiting" {
+        queue::waiting_ids(conn)?
+            .iter()
+            .position(|id| *id == submission_id)
+            .map(|index| index + 1)
+    } else {
+        None
+    };
+    publish(event_of(raw_submission, queue_position));
+
+    Ok(())
+}
+
+// The queue moved, tell every subscribed waiting submission its new position.
+pub fn publish_queue_positions(conn: &PgConnection) -> ServiceResult<()> {
+    if SUBMISSION_SUBSCRIBERS.read().unwrap().is_empty() {
+        return Ok(());
+    }
+
+    let waiting_ids = queue::waiting_ids(conn)?;
+    for (index, submission_id) in waiting_ids.iter().enumerate() {
+        let is_subscribed = SUBMISSION_SUBSCRIBERS
+            .read()
+            .unwrap()
+            .contains_key(submission_id);
+        if is_subscribed {
+            publish(SubmissionEvent {
+                id: *submission_id,
+                state: "Waiting".to_owned(),
+                queue_position: Some(index + 1),
+                result: None,
+                score: None,
+                err: None,
+            });
+        }
+    }
+
+    Ok(())
+}
@@ -1,105 +1,322 @@
-use crate::judge_actor::{handler::StartJudge, JudgeActorAddr};
+pub mod events;
+
+use crate::judge_actor::handler::{after_finish, score_result, StartJudge};
+use crate::judge_actor::{queue, JudgeActorAddr};
 use crate::models::utils::SizedList;
 use crate::models::*;
-use crate::statics::WAITING_QUEUE;
+use crate::statics::RUNNING_USERS;
 use actix_web::web;
 use diesel::prelude::*;
+use digest::Digest;
+use hex::ToHex;
+use md5::Md5;
 use server_core::database::{db_connection, Pool};
-use server_core::errors::ServiceResult;
+use server_core::errors::{ServiceError, ServiceResult};
 use server_core::utils::time::get_cur_naive_date_time;
 use std::fs::File;
 use std::io::prelude::*;
 use uuid::Uuid;
 
-pub fn create(
-    region: Option<String>,
+pub fn build_settings(
+    conn: &PgConnection,
     problem_id: i32,
-    user_id: i32,
     src: String,
-    language: String,
-    pool: web::Data<Pool>,
-    judge_actor: web::Data<JudgeActorAddr>,
-) -> ServiceResult<Uuid> {
-    let id = Uuid::new_v4();
-    let language_config = languages::get_lang_config(&language);
+    language: &str,
+) -> ServiceResult<submissions::JudgeSettings> {
+    let language = languages::get_language(language)?;
 
-    let conn = &db_connection(&pool)?;
     use crate::schema::problems as problems_schema;
-    use crate::schema::submissions as submissions_schema;
 
     let raw_problem: problems::RawProblem = problems_schema::table
         .filter(problems_schema::id.eq(problem_id))
         .first(conn)?;
     let problem = problems::Problem::from(raw_problem);
-    let mut spj_src = None;
-    if problem.settings.is_spj {
-        let mut file = File::open(format!("data/test_cases/{}/spj_src.cpp", problem.id))?;
+    let checker = if problem.settings.is_spj {
+        match problem.settings.checker_language.as_deref() {
+            Some(checker_language) => Some(languages::checker_config(checker_language)),
+            None => {
+                let hint = "Special judge has no compiled checker, import the problem again.";
+                return Err(ServiceError::InternalServerErrorWithHint(hint.to_owned()));
+            }
+        }
+    } else {
+        None
+    };
+    let mut interactor_src = None;
+    if problem.settings.is_interactive {
+        let mut file = File::open(format!(
+            "{}/interactor_src.cpp",
+            problems::test_case_dir(problem.id)
+        ))?;
         let mut contents = String::new();
         file.read_to_string(&mut contents)?;
-        spj_src = Some(contents);
+        interactor_src = Some(contents);
     }
 
-    let settings = submissions::JudgeSettings {
-        language_config: language_config,
-        src: src,
-        max_cpu_time: if &language == "c" || &language == "cpp" {
-            problem.settings.high_performance_max_cpu_time
-        } else {
-            problem.settings.other_max_cpu_time
-        },
-        max_memory: if &language == "c" || &language == "cpp" {
-            problem.settings.high_performance_max_memory
-        } else {
-            problem.settings.other_max_memory
+    let (max_cpu_time, max_memory) = languages::resolve_limits(
+        problem.settings.max_cpu_time,
+        problem.settings.max_memory,
+        &language.limits,
+        problem.settings.language_limits.get(&language.id),
+    );
+
+    Ok(submissions::JudgeSettings {
+        language_config: languages::LanguageConfig {
+            compile: language.compile,
+            run: language.run,
         },
+        src: src,
+        max_cpu_time,
+        max_memory,
         test_case_id: Some(problem.id.to_string()),
         test_case: None,
         spj_version: Some("1".to_owned()),
-        spj_config: if problem.settings.is_spj {
-            Some(languages::spj_config())
+        spj_config: None,
+        spj_compile_config: None,
+        spj_src: None,
+        checker,
+        interactor_config: if problem.settings.is_interactive {
+            Some(languages::interactor_config())
         } else {
             None
         },
-        spj_compile_config: if problem.settings.is_spj {
-            Some(languages::spj_compile_config())
+        interactor_compile_config: if problem.settings.is_interactive {
+            Some(languages::interactor_compile_config())
         } else {
             None
         },
-        spj_src: spj_src,
+        interactor_src,
+        comparator: match problem.settings.comparator {
+            problems::Comparator::Stripped => None,
+            comparator => Some(comparator),
+        },
         output: !problem.settings.opaque_output,
-    };
+    })
+}
+
+// identical submissions reuse a result finished within this window
+const CACHE_WINDOW_IN_HOURS: i64 = 24;
+
+// Digest of the judge settings and the version of the test data, None when the problem
+// was imported before test data had versions, so its results are never reused.
+pub fn content_hash(
+    conn: &PgConnection,
+    problem_id: i32,
+    settings: &submissions::JudgeSettings,
+) -> ServiceResult<Option<String>> {
+    use crate::schema::problems as problems_schema;
+
+    let raw_settings: String = problems_schema::table
+        .filter(problems_schema::id.eq(problem_id))
+        .select(problems_schema::settings)
+        .first(conn)?;
+    let problem_settings: problems::ProblemSettings =
+        serde_json::from_str(&raw_settings).map_err(|_| {
+            let hint = format!("Broken settings in problem {}.", problem_id);
+            ServiceError::InternalServerErrorWithHint(hint)
+        })?;
+
+    Ok(problem_settings.test_data_version.map(|test_data_version| {
+        let mut hasher = Md5::new();
+        hasher.update(test_data_version.as_bytes());
+        hasher.update(serde_json::to_string(settings).unwrap().as_bytes());
+        hasher.finalize().encode_hex::<String>()
+    }))
+}
+
+pub fn create(
+    region: Option<String>,
+    problem_id: i32,
+    user_id: i32,
+    src: String,
+    language: String,
+    pool: web::Data<Pool>,
+    judge_actor: web::Data<JudgeActorAddr>,
+) -> ServiceResult<Uuid> {
+    let id = Uuid::new_v4();
+
+    let conn = &db_connection(&pool)?;
+    use crate::schema::submissions as submissions_schema;
+
+    let settings = build_settings(conn, problem_id, src, &language)?;
 
     let settings_string = serde_json::to_string(&settings).unwrap();
 
-    diesel::insert_into(submissions_schema::table)
-        .values(&submissions::InsertableSubmission {
-            id: id,
-            problem_id: problem_id,
-            region: region,
-            user_id: user_id,
-            state: String::from("Waiting"),
-            settings: settings_string,
-            result: None,
-            submit_time: get_cur_naive_date_time(),
-            is_accepted: None,
-            finish_time: None,
-            max_time: None,
-            max_memory: None,
-            language: Some(language),
-            err: None,
-        })
-        .execute(conn)?;
-
-    {
-        let mut lock = WAITING_QUEUE.write().unwrap();
-        lock.push_back(id);
+    let content_hash = content_hash(conn, problem_id, &settings)?;
+    let cached_result: Option<String> = match content_hash.as_ref() {
+        Some(content_hash) => submissions_schema::table
+            .filter(submissions_schema::content_hash.eq(content_hash))
+            .filter(submissions_schema::state.eq("Finished".to_owned()))
+            // failures of judge servers and checkers may not happen again
+            .filter(
+                submissions_schema::err
+                    .is_null()
+                    .or(submissions_schema::err.eq(submissions::COMPILE_ERROR_ERR)),
+            )
+            .filter(
+                submissions_schema::finish_time
+                    .gt(get_cur_naive_date_time() - chrono::Duration::hours(CACHE_WINDOW_IN_HOURS)),
+            )
+            .order(submissions_schema::finish_time.desc())
+            .select(submissions_schema::result)
+            .first::<Option<String>>(conn)
+            .optional()?
+            .flatten(),
+        None => None,
+    };
+
+    let cached_result = match cached_result {
+        Some(cached_result) => {
+            let result: submissions::JudgeResult =
+                serde_json::from_str(&cached_result).map_err(|_| {
+                    let hint = "Broken result of a cached submission.".to_owned();
+                    ServiceError::InternalServerErrorWithHint(hint)
+                })?;
+            // so are those of checkers, found in the details only
+            let has_system_error = result
+                .details
+                .iter()
+                .flatten()
+                .any(|detail| detail.result == "SYSTEM_ERROR");
+            if has_system_error {
+                None
+            } else {
+                Some(result)
+            }
+        }
+        None => None,
+    };
+
+    if let Some(mut result) = cached_result {
+        conn.transaction::<_, ServiceError, _>(|| {
+            diesel::insert_into(submissions_schema::table)
+                .values(&submissions::InsertableSubmission {
+                    id,
+                    problem_id,
+                    region,
+                    user_id,
+                    state: String::from("Finished"),
+                    settings: settings_string,
+                    result: None,
+                    submit_time: get_cur_naive_date_time(),
+                    is_accepted: result.is_accepted,
+                    finish_time: Some(get_cur_naive_date_time()),
+                    max_time: result.max_time,
+                    max_memory: result.max_memory,
+                    language: Some(language),
+                    err: result.err.clone(),
+                    content_hash,
+                    is_cached: true,
+                })
+                .execute(conn)?;
+
+            // the full score and subtasks may differ from those of the cached submission
+            let score = score_result(conn, id, &settings, &mut result)?;
+            let out_results: Option<Vec<String>> = result
+                .out_results()
+                .map(|result_set| result_set.into_iter().collect());
+            diesel::update(submissions_schema::table.filter(submissions_schema::id.eq(id)))
+                .set((
+                    submissions_schema::result.eq(serde_json::to_string(&result).unwrap()),
+                    submissions_schema::out_results.eq(out_results),
+                    submissions_schema::score.eq(Some(score)),
+                ))
+                .execute(conn)?;
+
+            Ok(())
+        })?;
+
+        info!("reused a cached result for {}", id);
+        after_finish(conn, id)?;
+        return Ok(id);
     }
 
+    let lane = queue::lane_of(conn, region.as_deref())?;
+
+    conn.transaction::<_, ServiceError, _>(|| {
+        diesel::insert_into(submissions_schema::table)
+            .values(&submissions::InsertableSubmission {
+                id: id,
+                problem_id: problem_id,
+                region: region,
+                user_id: user_id,
+                state: String::from("Waiting"),
+                settings: settings_string,
+                result: None,
+                submit_time: get_cur_naive_date_time(),
+                is_accepted: None,
+                finish_time: None,
+                max_time: None,
+                max_memory: None,
+                language: Some(language),
+                err: None,
+                content_hash,
+                is_cached: false,
+            })
+            .execute(conn)?;
+
+        queue::push(conn, id, lane)
+    })?;
+
     judge_actor.addr.do_send(StartJudge());
 
     Ok(id)
 }
 
+// A user running code on custom input, until this is dropped.
+pub struct RunningUser(i32);
+
+impl Drop for RunningUser {
+    fn drop(&mut self) {
+        RUNNING_USERS.write().unwrap().remove(&self.0);
+    }
+}
+
+// Runs skip the queue and hold a judge actor till they finish, so a user has one at a time.
+pub fn start_run(user_id: i32) -> ServiceResult<RunningUser> {
+    if !RUNNING_USERS.write().unwrap().insert(user_id) {
+        let hint = "Another run of yours is not finished yet.".to_owned();
+        return Err(ServiceError::BadRequest(hint));
+    }
+    Ok(RunningUser(user_id))
+}
+
+// custom input is kept small, it is sent along to the judge server
+const MAX_RUN_INPUT_SIZE: usize = 1024 * 1024;
+
+// Settings to run the source on custom input with limits of the problem,
+// nothing is written to submissions.
+pub fn build_run_settings(
+    problem_id: i32,
+    src: String,
+    language: String,
+    input: String,
+    pool: web::Data<Pool>,
+) -> ServiceResult<submissions::JudgeSettings> {
+    if input.len() > MAX_RUN_INPUT_SIZE {
+        let hint = format!("Input is longer than {} bytes.", MAX_RUN_INPUT_SIZE);
+        return Err(ServiceError::BadRequest(hint));
+    }
+
+    let conn = &db_connection(&pool)?;
+    let mut settings = build_settings(conn, problem_id, src, &language)?;
+    if settings.interactor_config.is_some() {
+        let hint = "Interactive problems can not be run on custom input.".to_owned();
+        return Err(ServiceError::BadRequest(hint));
+    }
+
+    settings.test_case_id = None;
+    settings.test_case = Some(vec![submissions::TestCase {
+        input,
+        output: String::new(),
+    }]);
+    settings.checker = None;
+    settings.comparator = None;
+    settings.output = true;
+
+    Ok(settings)
+}
+
 pub fn get(id: Uuid, pool: web::Data<Pool>) -> ServiceResult<submissions::Submission> {
     let conn = &db_connection(&pool)?;
 
@@ -112,6 +329,34 @@ pub fn get(id: Uuid, pool: web::Data<Pool>) -> ServiceResult<submissions::Submis
     Ok(submissions::Submission::from(raw))
 }
 
+pub fn get_failed_list(
+    limit: i32,
+    offset: i32,
+    pool: web::Data<Pool>,
+) -> ServiceResult<SizedList<submissions::SlimSubmission>> {
+    let conn = &db_connection(&pool)?;
+
+    use crate::schema::submissions as submissions_schema;
+
+    let target =
+        submissions_schema::table.filter(submissions_schema::state.eq("SystemError".to_owned()));
+
+    let total: i64 = target.clone().count().get_result(conn)?;
+
+    let raw_submissions: Vec<submissions::RawSubmission> = target
+        .offset(offset.into())
+        .limit(limit.into())
+        .order(submissions_schema::finish_time.desc())
+        .load(conn)?;
+
+    let mut res = Vec::new();
+    for raw_submission in raw_submissions {
+        res.push(submissions::SlimSubmission::from(raw_submission));
+    }
+
+    Ok(SizedList { total, list: res })
+}
+
 pub fn get_list(
     region_filter: Option<String>,
     problem_id_filter: Option<i32>,
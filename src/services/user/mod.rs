@@ -5,6 +5,7 @@ use diesel::prelude::*;
 use server_core::database::{db_connection, Pool};
 use server_core::errors::{ServiceError, ServiceResult};
 use server_core::utils::encryption;
+use std::collections::BTreeMap;
 
 pub fn create(
     account: String,
@@ -321,3 +322,46 @@ pub fn get_submissions_time(
 
     Ok(time_count)
 }
+
+pub fn get_best_scores(
+    user_id: i32,
+    region: Option<String>,
+    pool: web::Data<Pool>,
+) -> ServiceResult<Vec<UserProblemScore>> {
+    let conn = &db_connection(&pool)?;
+
+    use crate::schema::submissions as submissions_schema;
+
+    let raw_scores: Vec<(Option<String>, i32, Option<i32>)> = submissions_schema::table
+        .filter(submissions_schema::user_id.eq(user_id))
+        .filter(submissions_schema::region.is_not_null())
+        .filter(
+            submissions_schema::region
+                .eq(region.clone())
+                .or(region.is_none()),
+        )
+        .filter(submissions_schema::score.is_not_null())
+        .select((
+            submissions_schema::region,
+            submissions_schema::problem_id,
+            submissions_schema::score,
+        ))
+        .load(conn)?;
+
+    let mut best_scores: BTreeMap<(String, i32), i32> = BTreeMap::new();
+    for (region, problem_id, score) in raw_scores {
+        if let (Some(region), Some(score)) = (region, score) {
+            let best_score = best_scores.entry((region, problem_id)).or_insert(score);
+            *best_score = (*best_score).max(score);
+        }
+    }
+
+    Ok(best_scores
+        .into_iter()
+        .map(|((region, problem_id), best_score)| UserProblemScore {
+            region,
+            problem_id,
+            best_score,
+        })
+        .collect())
+}
@@ -1,22 +1,30 @@
 use crate::models::{
-    judge_servers::JudgeServerInfo, ranks::ACMRank, statistics::SubmissionStatistics,
+    judge_servers::{JudgeServerInfo, JudgeServerTokenConfig},
+    languages::{load_languages, LanguageRegistry},
+    ranks::ACMRank,
+    statistics::SubmissionStatistics,
+    submissions::SubmissionEvent,
     users::AuthConfig,
 };
+use futures::channel::mpsc::UnboundedSender;
 use regex::Regex;
 use std::io::Read;
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet},
     sync::RwLock,
 };
 use uuid::Uuid;
 
 lazy_static! {
-    pub static ref WAITING_QUEUE: RwLock<VecDeque::<Uuid>> = RwLock::new(VecDeque::new());
     pub static ref RESULT_STATISTICS_CACHE: RwLock<HashMap<(String, i32), SubmissionStatistics>> =
         RwLock::new(HashMap::new());
     pub static ref ACM_RANK_CACHE: RwLock<HashMap<String, ACMRank>> = RwLock::new(HashMap::new());
     pub static ref JUDGE_SERVER_INFOS: RwLock<HashMap<String, JudgeServerInfo>> =
         RwLock::new(HashMap::new());
+    pub static ref SUBMISSION_SUBSCRIBERS: RwLock<HashMap<Uuid, Vec<UnboundedSender<SubmissionEvent>>>> =
+        RwLock::new(HashMap::new());
+    // users running code on custom input, see services::submission
+    pub static ref RUNNING_USERS: RwLock<HashSet<i32>> = RwLock::new(HashSet::new());
     pub static ref RE_EMAIL: Regex =
         Regex::new(r"^\w+([-+.]\w+)*@\w+([-.]\w+)*\.\w+([-.]\w+)*$").unwrap();
     pub static ref RE_MOBILE: Regex =
@@ -28,4 +36,29 @@ lazy_static! {
         file.read_to_string(&mut content).unwrap();
         toml::from_str(&content).unwrap()
     };
+    pub static ref JUDGE_SERVER_TOKENS: JudgeServerTokenConfig = {
+        match std::fs::read_to_string("judge_servers.toml") {
+            Ok(content) => match toml::from_str(&content) {
+                Ok(config) => config,
+                Err(e) => {
+                    log::error!("Can not parse judge_servers.toml ({}), no judge server will be accepted.", e);
+                    JudgeServerTokenConfig::default()
+                }
+            },
+            Err(_) => {
+                log::warn!("judge_servers.toml not found, no judge server will be accepted.");
+                JudgeServerTokenConfig::default()
+            }
+        }
+    };
+    // reloaded by admins, see services::language
+    pub static ref LANGUAGES: RwLock<LanguageRegistry> = {
+        match load_languages() {
+            Ok(registry) => RwLock::new(registry),
+            Err(e) => {
+                log::warn!("Can not load languages.toml ({}), no language will be accepted.", e);
+                RwLock::new(LanguageRegistry::default())
+            }
+        }
+    };
 }